@@ -0,0 +1,713 @@
+// compact binary and json encodings of a `GameState`, for storing and sharing large batches of boards.
+//
+// binary layout, little endian:
+//   magic `MSWP`, version: u16, width: u32, height: u32,
+//   from version 2 on:
+//     topology: u8 (bit 0 wraps x, bit 1 wraps y),
+//     neighborhood: u8 (0 moore, 1 hex, 2 kernel),
+//     for kernels, the number of offsets: u8, followed by each offset as x: i8, y: i8,
+//     depth: u32,
+//     mines per cell: u8,
+//   mines: 1 bit per cell, or 2, 4 or 8 bits when a cell can hold more than one mine, padded to a
+//   whole byte,
+//   visibility: 2 bits per cell (0 unknown, 1 flagged, 2 revealed), padded to a whole byte,
+//   flags: as many bits per cell as the mines, only present when a cell can hold more than one mine.
+// revealed numbers are not stored, since they follow from the mine layout.
+//
+// json records store mines as cell indices and the visible board as one string per row,
+// using the symbols of the text format (`#` unknown, `F` flagged, `.`, digits and letters revealed).
+// a cell with several mines is listed once per mine, and a cell with several flags is listed in
+// `flags` once per flag beyond the first.
+// records without a topology are bounded, records without a neighborhood are moore, records
+// without a depth are 2d and records without mines per cell hold one mine per cell.
+// several boards can be stored back to back in one binary stream or as a json array of records.
+
+use std::{
+    convert::TryFrom,
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::*;
+use crate::text_format::{number_symbol, symbol_number};
+
+pub const FORMAT_VERSION: u16 = 2;
+const MAGIC: &[u8; 4] = b"MSWP";
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Game(GameError),
+    BadMagic,
+    UnsupportedVersion(u16),
+    FieldLength {
+        expected: usize,
+        found: usize,
+    },
+    InvalidCell {
+        pos: (usize, usize),
+        symbol: char,
+    },
+    MineOutOfRange(usize),
+    InvalidNeighborhood(String),
+    InvalidDepth {
+        height: usize,
+        depth: usize,
+    },
+    InvalidMinesPerCell(usize),
+    // more mines or flags in a cell than the board's mines per cell allow.
+    OverfullCell {
+        pos: (usize, usize),
+        count: usize,
+        mines_per_cell: usize,
+    },
+    StrayFlag {
+        pos: (usize, usize),
+    },
+    InconsistentNumber {
+        pos: (usize, usize),
+        written: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Io(e) => write!(f, "{}", e),
+            DecodeError::Json(e) => write!(f, "{}", e),
+            DecodeError::Game(e) => write!(f, "{}", e),
+            DecodeError::BadMagic => write!(f, "not a minesweeper board"),
+            DecodeError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported format version {}, expected at most {}",
+                version, FORMAT_VERSION
+            ),
+            DecodeError::FieldLength { expected, found } => write!(
+                f,
+                "board dimensions call for {} cells but {} were found",
+                expected, found
+            ),
+            DecodeError::InvalidCell { pos, symbol } => {
+                write!(f, "invalid cell {:?} at {:?}", symbol, pos)
+            }
+            DecodeError::MineOutOfRange(index) => {
+                write!(f, "mine at index {} is outside of the board", index)
+            }
+            DecodeError::InvalidNeighborhood(e) => write!(f, "{}", e),
+            DecodeError::InvalidDepth { height, depth } => {
+                write!(f, "{} rows can't be split into {} layers", height, depth)
+            }
+            DecodeError::InvalidMinesPerCell(mines_per_cell) => write!(
+                f,
+                "the numbers of a board with {} mines per cell don't fit the format",
+                mines_per_cell
+            ),
+            DecodeError::OverfullCell {
+                pos,
+                count,
+                mines_per_cell,
+            } => write!(
+                f,
+                "cell {:?} holds {} mines or flags, but at most {} fit in a cell",
+                pos, count, mines_per_cell
+            ),
+            DecodeError::StrayFlag { pos } => {
+                write!(f, "cell {:?} has a flag but isn't flagged", pos)
+            }
+            DecodeError::InconsistentNumber {
+                pos,
+                written,
+                actual,
+            } => write!(
+                f,
+                "cell {:?} shows {} but has {} neighboring mines",
+                pos, written, actual
+            ),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl From<io::Error> for DecodeError {
+    fn from(e: io::Error) -> Self {
+        DecodeError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for DecodeError {
+    fn from(e: serde_json::Error) -> Self {
+        DecodeError::Json(e)
+    }
+}
+
+impl From<GameError> for DecodeError {
+    fn from(e: GameError) -> Self {
+        DecodeError::Game(e)
+    }
+}
+
+fn pack_bits(values: impl Iterator<Item = u8>, bits: usize) -> Vec<u8> {
+    let per_byte = 8 / bits;
+    let mut packed = Vec::new();
+    for (i, value) in values.enumerate() {
+        if i % per_byte == 0 {
+            packed.push(0);
+        }
+        *packed.last_mut().unwrap() |= value << ((i % per_byte) * bits);
+    }
+    packed
+}
+
+fn unpack_bits(packed: &[u8], bits: usize, count: usize) -> Vec<u8> {
+    let per_byte = 8 / bits;
+    let mask = (1u8 << bits) - 1;
+    (0..count)
+        .map(|i| (packed[i / per_byte] >> ((i % per_byte) * bits)) & mask)
+        .collect()
+}
+
+// a truncated stream means the dimensions don't match the data that follows them.
+fn read_packed<R: Read>(reader: &mut R, bits: usize, count: usize) -> Result<Vec<u8>, DecodeError> {
+    let per_byte = 8 / bits;
+    let expected = count.div_ceil(per_byte);
+    let mut packed = Vec::new();
+    let found = reader.take(expected as u64).read_to_end(&mut packed)?;
+    if found < expected {
+        return Err(DecodeError::FieldLength {
+            expected: count,
+            found: found * per_byte,
+        });
+    }
+    Ok(packed)
+}
+
+// the bits used for the mine and flag counts of each cell.
+fn count_bits(mines_per_cell: usize) -> usize {
+    match mines_per_cell {
+        0..=1 => 1,
+        2..=3 => 2,
+        4..=15 => 4,
+        _ => 8,
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+// the board needs cells, the layers have to split the rows evenly, the neighborhood has to suit the
+// topology, and its numbers have to fit the formats.
+fn check_shape(
+    width: usize,
+    height: usize,
+    depth: usize,
+    topology: Topology,
+    neighborhood: &Neighborhood,
+    mines_per_cell: usize,
+) -> Result<(), DecodeError> {
+    GameState::check_dimensions(width, height, 0, 1)?;
+    if depth == 0 || !height.is_multiple_of(depth) {
+        return Err(DecodeError::InvalidDepth { height, depth });
+    }
+    neighborhood
+        .check()
+        .and_then(|_| neighborhood.check_topology(topology, height / depth))
+        .map_err(DecodeError::InvalidNeighborhood)?;
+    if neighborhood.reach(depth) > MAX_NEIGHBORS {
+        return Err(DecodeError::InvalidNeighborhood(format!(
+            "{} has more than {} neighbors on a board with {} layers",
+            neighborhood, MAX_NEIGHBORS, depth
+        )));
+    }
+    if mines_per_cell == 0 || neighborhood.reach(depth) * mines_per_cell > MAX_NEIGHBORS {
+        return Err(DecodeError::InvalidMinesPerCell(mines_per_cell));
+    }
+    Ok(())
+}
+
+// every cell has to fit within the mines per cell that the board declares.
+fn check_cells(game_state: &GameState, mines_per_cell: usize) -> Result<(), DecodeError> {
+    for (i, cell) in game_state.field.iter().enumerate() {
+        let count = cell.state.mines().max(cell.visibility.flags());
+        if count > mines_per_cell {
+            return Err(DecodeError::OverfullCell {
+                pos: (i % game_state.width, i / game_state.width),
+                count,
+                mines_per_cell,
+            });
+        }
+    }
+    Ok(())
+}
+
+// revealed numbers always have to agree with the mine layout, no matter where the board came from.
+fn check_numbers(game_state: &GameState) -> Result<(), DecodeError> {
+    for (i, cell) in game_state.field.iter().enumerate() {
+        if let CellVisibility::Empty(written) = cell.visibility {
+            let pos = (i % game_state.width, i / game_state.width);
+            let actual = game_state.neighboring_mines(pos.0, pos.1);
+            if written != actual {
+                return Err(DecodeError::InconsistentNumber {
+                    pos,
+                    written,
+                    actual,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+impl GameState {
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        if self.mines_per_cell > u8::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                DecodeError::InvalidMinesPerCell(self.mines_per_cell),
+            ));
+        }
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.width as u32).to_le_bytes())?;
+        writer.write_all(&(self.height as u32).to_le_bytes())?;
+        writer.write_all(&[self.topology.wrap_x as u8 | (self.topology.wrap_y as u8) << 1])?;
+        match &self.neighborhood {
+            Neighborhood::Moore => writer.write_all(&[0])?,
+            Neighborhood::Hex => writer.write_all(&[1])?,
+            Neighborhood::Kernel(offsets) => {
+                writer.write_all(&[2, offsets.len() as u8])?;
+                for (x_offset, y_offset) in offsets {
+                    writer.write_all(&[*x_offset as i8 as u8, *y_offset as i8 as u8])?;
+                }
+            }
+        }
+        writer.write_all(&(self.depth as u32).to_le_bytes())?;
+        writer.write_all(&[self.mines_per_cell as u8])?;
+        let bits = count_bits(self.mines_per_cell);
+        writer.write_all(&pack_bits(
+            self.field.iter().map(|c| c.state.mines() as u8),
+            bits,
+        ))?;
+        writer.write_all(&pack_bits(
+            self.field.iter().map(|c| match c.visibility {
+                CellVisibility::Unknown => 0,
+                CellVisibility::Flagged(_) => 1,
+                CellVisibility::Empty(_) => 2,
+            }),
+            2,
+        ))?;
+        if self.mines_per_cell > 1 {
+            writer.write_all(&pack_bits(
+                self.field.iter().map(|c| c.visibility.flags() as u8),
+                bits,
+            ))?;
+        }
+        Ok(())
+    }
+
+    pub fn read_binary<R: Read>(reader: &mut R) -> Result<GameState, DecodeError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+        let mut version = [0u8; 2];
+        reader.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let width = read_u32(reader)? as usize;
+        let height = read_u32(reader)? as usize;
+        // version 1 boards are bounded 2d moore boards with one mine per cell.
+        let (topology, neighborhood, depth, mines_per_cell) = if version >= 2 {
+            let mut topology = [0u8; 1];
+            reader.read_exact(&mut topology)?;
+            let topology = Topology {
+                wrap_x: topology[0] & 1 != 0,
+                wrap_y: topology[0] & 2 != 0,
+            };
+            let mut neighborhood = [0u8; 1];
+            reader.read_exact(&mut neighborhood)?;
+            let neighborhood = match neighborhood[0] {
+                0 => Neighborhood::Moore,
+                1 => Neighborhood::Hex,
+                2 => {
+                    let mut count = [0u8; 1];
+                    reader.read_exact(&mut count)?;
+                    let mut offsets = vec![0u8; 2 * count[0] as usize];
+                    reader.read_exact(&mut offsets)?;
+                    Neighborhood::Kernel(
+                        offsets
+                            .chunks(2)
+                            .map(|o| (o[0] as i8 as isize, o[1] as i8 as isize))
+                            .collect(),
+                    )
+                }
+                kind => {
+                    return Err(DecodeError::InvalidNeighborhood(format!(
+                        "unknown neighborhood {}",
+                        kind
+                    )))
+                }
+            };
+            let depth = read_u32(reader)? as usize;
+            let mut mines_per_cell = [0u8; 1];
+            reader.read_exact(&mut mines_per_cell)?;
+            (topology, neighborhood, depth, mines_per_cell[0] as usize)
+        } else {
+            (Topology::BOUNDED, Neighborhood::Moore, 1, 1)
+        };
+        check_shape(
+            width,
+            height,
+            depth,
+            topology,
+            &neighborhood,
+            mines_per_cell,
+        )?;
+        let count = width * height;
+        let bits = count_bits(mines_per_cell);
+
+        let mines = read_packed(reader, bits, count)?;
+        let visibility = read_packed(reader, 2, count)?;
+        let flags = if mines_per_cell > 1 {
+            unpack_bits(&read_packed(reader, bits, count)?, bits, count)
+        } else {
+            vec![1; count]
+        };
+
+        let mines = unpack_bits(&mines, bits, count);
+        let visibility = unpack_bits(&visibility, 2, count);
+        let mut field = Vec::with_capacity(count);
+        for (i, (mine, visibility)) in mines.into_iter().zip(visibility).enumerate() {
+            if mines_per_cell > 1 && visibility != 1 && flags[i] > 0 {
+                return Err(DecodeError::StrayFlag {
+                    pos: (i % width, i / width),
+                });
+            }
+            let visibility = match visibility {
+                0 => CellVisibility::Unknown,
+                1 if flags[i] > 0 => CellVisibility::Flagged(flags[i] as usize),
+                2 => CellVisibility::Empty(0),
+                _ => {
+                    return Err(DecodeError::InvalidCell {
+                        pos: (i % width, i / width),
+                        symbol: char::from(b'0' + visibility),
+                    })
+                }
+            };
+            field.push(Cell {
+                state: match mine {
+                    0 => CellState::Empty,
+                    mines => CellState::Mine(mines as usize),
+                },
+                visibility,
+            });
+        }
+        let mut game_state = GameState::from_field(width, height, field);
+        check_cells(&game_state, mines_per_cell)?;
+        game_state.topology = topology;
+        game_state.neighborhood = neighborhood;
+        game_state.depth = depth;
+        game_state.mines_per_cell = mines_per_cell;
+        // revealed numbers are recomputed from the mines.
+        for i in 0..count {
+            if let CellVisibility::Empty(_) = game_state.field[i].visibility {
+                let n = game_state.neighboring_mines(i % width, i / width);
+                game_state.field[i].visibility = CellVisibility::Empty(n);
+            }
+        }
+        Ok(game_state)
+    }
+
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_binary(&mut bytes).unwrap();
+        bytes
+    }
+
+    pub fn from_binary(mut bytes: &[u8]) -> Result<GameState, DecodeError> {
+        GameState::read_binary(&mut bytes)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&GameRecord::from(self)).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<GameState, DecodeError> {
+        GameState::try_from(serde_json::from_str::<GameRecord>(json)?)
+    }
+}
+
+// reads every board of a binary stream, stopping at the end of the stream.
+pub fn read_binary_batch<R: Read>(reader: &mut R) -> Result<Vec<GameState>, DecodeError> {
+    let mut boards = Vec::new();
+    let mut reader = io::BufReader::new(reader);
+    loop {
+        let mut first = [0u8; 1];
+        if reader.read(&mut first)? == 0 {
+            return Ok(boards);
+        }
+        boards.push(GameState::read_binary(
+            &mut (&first[..]).chain(&mut reader),
+        )?);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub version: u16,
+    pub width: usize,
+    pub height: usize,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub neighborhood: Neighborhood,
+    #[serde(default = "default_depth")]
+    pub depth: usize,
+    #[serde(default = "default_mines_per_cell")]
+    pub mines_per_cell: usize,
+    pub mines: Vec<usize>,
+    pub visible: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<usize>,
+}
+
+fn default_depth() -> usize {
+    1
+}
+
+fn default_mines_per_cell() -> usize {
+    1
+}
+
+impl From<&GameState> for GameRecord {
+    fn from(game_state: &GameState) -> Self {
+        let mines = game_state
+            .field
+            .iter()
+            .enumerate()
+            .flat_map(|(i, c)| std::iter::repeat_n(i, c.state.mines()))
+            .collect();
+        let flags = game_state
+            .field
+            .iter()
+            .enumerate()
+            .flat_map(|(i, c)| std::iter::repeat_n(i, c.visibility.flags().saturating_sub(1)))
+            .collect();
+        let visible = game_state
+            .field
+            .chunks(game_state.width)
+            .map(|row| {
+                row.iter()
+                    .map(|c| match c.visibility {
+                        CellVisibility::Unknown => '#',
+                        CellVisibility::Flagged(_) => 'F',
+                        CellVisibility::Empty(n) => number_symbol(n),
+                    })
+                    .collect()
+            })
+            .collect();
+        GameRecord {
+            version: FORMAT_VERSION,
+            width: game_state.width,
+            height: game_state.height,
+            topology: game_state.topology,
+            neighborhood: game_state.neighborhood.clone(),
+            depth: game_state.depth,
+            mines_per_cell: game_state.mines_per_cell,
+            mines,
+            visible,
+            flags,
+        }
+    }
+}
+
+impl TryFrom<GameRecord> for GameState {
+    type Error = DecodeError;
+
+    fn try_from(record: GameRecord) -> Result<Self, Self::Error> {
+        if record.version == 0 || record.version > FORMAT_VERSION {
+            return Err(DecodeError::UnsupportedVersion(record.version));
+        }
+        let (width, height) = (record.width, record.height);
+        check_shape(
+            width,
+            height,
+            record.depth,
+            record.topology,
+            &record.neighborhood,
+            record.mines_per_cell,
+        )?;
+        let cells: Vec<char> = record.visible.iter().flat_map(|row| row.chars()).collect();
+        if cells.len() != width * height
+            || record.visible.iter().any(|r| r.chars().count() != width)
+        {
+            return Err(DecodeError::FieldLength {
+                expected: width * height,
+                found: cells.len(),
+            });
+        }
+        let mut field = Vec::with_capacity(cells.len());
+        for (i, symbol) in cells.into_iter().enumerate() {
+            let visibility = match symbol {
+                '#' => CellVisibility::Unknown,
+                'F' => CellVisibility::Flagged(1),
+                _ if symbol_number(symbol).is_some() => {
+                    CellVisibility::Empty(symbol_number(symbol).unwrap())
+                }
+                _ => {
+                    return Err(DecodeError::InvalidCell {
+                        pos: (i % width, i / width),
+                        symbol,
+                    })
+                }
+            };
+            field.push(Cell {
+                state: CellState::Empty,
+                visibility,
+            });
+        }
+        for index in record.mines {
+            let cell = field
+                .get_mut(index)
+                .ok_or(DecodeError::MineOutOfRange(index))?;
+            cell.state = CellState::Mine(cell.state.mines() + 1);
+        }
+        for index in record.flags {
+            let cell = field
+                .get_mut(index)
+                .ok_or(DecodeError::MineOutOfRange(index))?;
+            match cell.visibility {
+                CellVisibility::Flagged(flags) => {
+                    cell.visibility = CellVisibility::Flagged(flags + 1)
+                }
+                _ => {
+                    return Err(DecodeError::StrayFlag {
+                        pos: (index % width, index / width),
+                    })
+                }
+            }
+        }
+        let mut game_state = GameState::from_field(width, height, field);
+        check_cells(&game_state, record.mines_per_cell)?;
+        game_state.topology = record.topology;
+        game_state.neighborhood = record.neighborhood;
+        game_state.depth = record.depth;
+        game_state.mines_per_cell = record.mines_per_cell;
+        check_numbers(&game_state)?;
+        Ok(game_state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> GameState {
+        "5 3 3\n*2.#*\nF2.1#\n11..#".parse().unwrap()
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let game_state = sample();
+        let bytes = game_state.to_binary();
+        // 21 byte header, 2 bytes of mines and 4 bytes of visibility for 15 cells.
+        assert_eq!(bytes.len(), 21 + 2 + 4);
+        let decoded = GameState::from_binary(&bytes).unwrap();
+        assert_eq!(decoded.to_string(), game_state.to_string());
+
+        let mut batch = bytes.clone();
+        batch.extend(&bytes);
+        assert_eq!(read_binary_batch(&mut &batch[..]).unwrap().len(), 2);
+        assert!(matches!(
+            GameState::from_binary(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::FieldLength { .. })
+        ));
+        let mut empty = bytes.clone();
+        empty[6..10].copy_from_slice(&0u32.to_le_bytes());
+        assert!(matches!(
+            GameState::from_binary(&empty),
+            Err(DecodeError::Game(GameError::InvalidDimensions { .. }))
+        ));
+
+        // version 1 boards have no topology, neighborhood, depth or mines per cell.
+        let mut old = bytes.clone();
+        old[4..6].copy_from_slice(&1u16.to_le_bytes());
+        old.drain(14..21);
+        assert_eq!(
+            GameState::from_binary(&old).unwrap().to_string(),
+            game_state.to_string()
+        );
+
+        let mut torus = game_state;
+        torus.topology = Topology::TORUS;
+        torus.neighborhood = Neighborhood::knight();
+        torus.depth = 3;
+        let decoded = GameState::from_binary(&torus.to_binary()).unwrap();
+        assert_eq!(decoded.depth, 3);
+        assert_eq!(decoded.topology, Topology::TORUS);
+        assert_eq!(decoded.neighborhood, Neighborhood::knight());
+
+        let stacked: GameState = "3 1 4 mines-per-cell-3\n{F**}4{Ff}".parse().unwrap();
+        let decoded = GameState::from_binary(&stacked.to_binary()).unwrap();
+        assert_eq!(decoded.to_string(), stacked.to_string());
+        // the three mines of the first cell don't fit in a board with two mines per cell.
+        let mut overfull = stacked.to_binary();
+        overfull[20] = 2;
+        assert!(matches!(
+            GameState::from_binary(&overfull),
+            Err(DecodeError::OverfullCell { pos: (0, 0), .. })
+        ));
+        let mut huge = stacked.clone();
+        huge.mines_per_cell = 256;
+        assert!(huge.write_binary(&mut Vec::new()).is_err());
+        assert_eq!(
+            GameState::from_json(&stacked.to_json())
+                .unwrap()
+                .to_string(),
+            stacked.to_string()
+        );
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let game_state = sample();
+        let decoded = GameState::from_json(&game_state.to_json()).unwrap();
+        assert_eq!(decoded.to_string(), game_state.to_string());
+
+        let mut record = GameRecord::from(&game_state);
+        record.visible[0] = "#3.##".to_string();
+        assert!(matches!(
+            GameState::try_from(record),
+            Err(DecodeError::InconsistentNumber { pos: (1, 0), .. })
+        ));
+        let mut record = GameRecord::from(&game_state);
+        record.flags.push(3);
+        assert!(matches!(
+            GameState::try_from(record),
+            Err(DecodeError::StrayFlag { pos: (3, 0) })
+        ));
+        let mut record = GameRecord::from(&game_state);
+        record.mines.push(0);
+        assert!(matches!(
+            GameState::try_from(record),
+            Err(DecodeError::OverfullCell { pos: (0, 0), .. })
+        ));
+        let mut record = GameRecord::from(&game_state);
+        record.height = 0;
+        assert!(matches!(
+            GameState::try_from(record),
+            Err(DecodeError::Game(GameError::InvalidDimensions { .. }))
+        ));
+    }
+}
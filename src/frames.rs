@@ -0,0 +1,214 @@
+// records the boards of a solver run as an animated gif per game, or as numbered image files.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use crate::game::GameState;
+use crate::render::TileRenderer;
+use crate::snapshot::{ImageFormat, Snapshot};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameFormat {
+    Gif,
+    Images(ImageFormat),
+}
+
+impl FromStr for FrameFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gif" => Ok(FrameFormat::Gif),
+            _ => s.parse().map(FrameFormat::Images).map_err(|_| {
+                format!(
+                    "unknown frame format {:?}, expected `gif`, `png` or `ppm`",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+fn gif_error(e: gif::EncodingError) -> io::Error {
+    match e {
+        gif::EncodingError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    }
+}
+
+// gif frames are indexed, so boards with at most 256 colours are stored exactly and anything
+// else (e.g. a heat map) is quantized.
+fn gif_frame(snapshot: &Snapshot) -> gif::Frame<'static> {
+    let (width, height) = (snapshot.width as u16, snapshot.height as u16);
+    let mut palette: BTreeMap<u32, u8> = BTreeMap::new();
+    for p in snapshot.pixels.iter() {
+        if palette.len() > 256 {
+            break;
+        }
+        let next = palette.len() as u8;
+        palette.entry(*p).or_insert(next);
+    }
+    if palette.len() > 256 {
+        return gif::Frame::from_rgb_speed(width, height, &snapshot.rgb_bytes(), 10);
+    }
+    let indices: Vec<u8> = snapshot.pixels.iter().map(|p| palette[p]).collect();
+    let mut colors = vec![0u8; palette.len() * 3];
+    for (p, i) in palette.iter() {
+        let i = *i as usize * 3;
+        colors[i..i + 3].copy_from_slice(&[(p >> 16) as u8, (p >> 8) as u8, *p as u8]);
+    }
+    gif::Frame::from_palette_pixels(width, height, &indices, &colors, None)
+}
+
+pub struct FrameRecorder {
+    dir: PathBuf,
+    format: FrameFormat,
+    // a frame is captured every `every` applied events.
+    every: usize,
+    renderer: TileRenderer,
+    // hundredths of a second between gif frames.
+    delay: u16,
+    game: usize,
+    events: usize,
+    frames: usize,
+    encoder: Option<gif::Encoder<BufWriter<File>>>,
+}
+
+impl FrameRecorder {
+    pub fn new(
+        dir: PathBuf,
+        format: FrameFormat,
+        every: usize,
+        renderer: TileRenderer,
+    ) -> io::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(FrameRecorder {
+            dir,
+            format,
+            every: every.max(1),
+            renderer,
+            delay: 5,
+            game: 0,
+            events: 0,
+            frames: 0,
+            encoder: None,
+        })
+    }
+
+    // counts an applied event, and returns whether a frame is due.
+    pub fn tick(&mut self) -> bool {
+        self.events += 1;
+        self.events.is_multiple_of(self.every)
+    }
+
+    // `cell_pixels` holds one colour per cell, as drawn for the window.
+    pub fn capture(&mut self, game_state: &GameState, cell_pixels: &[u32]) -> io::Result<()> {
+        let snapshot = Snapshot::from_cell_pixels(game_state, &self.renderer, cell_pixels);
+        match self.format {
+            FrameFormat::Gif => {
+                if self.encoder.is_none() {
+                    let path = self.dir.join(format!("game_{:05}.gif", self.game));
+                    let mut encoder = gif::Encoder::new(
+                        BufWriter::new(File::create(path)?),
+                        snapshot.width as u16,
+                        snapshot.height as u16,
+                        &[],
+                    )
+                    .map_err(gif_error)?;
+                    encoder
+                        .set_repeat(gif::Repeat::Infinite)
+                        .map_err(gif_error)?;
+                    self.encoder = Some(encoder);
+                }
+                let mut frame = gif_frame(&snapshot);
+                frame.delay = self.delay;
+                self.encoder
+                    .as_mut()
+                    .unwrap()
+                    .write_frame(&frame)
+                    .map_err(gif_error)?;
+            }
+            FrameFormat::Images(format) => {
+                let path = self.dir.join(format!(
+                    "game_{:05}_frame_{:05}.{}",
+                    self.game,
+                    self.frames,
+                    format.extension()
+                ));
+                snapshot.save(path)?;
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    // close the current game's recording. the next capture starts a new game.
+    pub fn finish_game(&mut self) -> io::Result<()> {
+        let encoder = self.encoder.take();
+        let had_frames = self.frames > 0;
+        self.events = 0;
+        self.frames = 0;
+        if had_frames {
+            self.game += 1;
+        }
+        if let Some(encoder) = encoder {
+            // `into_inner` writes the gif trailer.
+            let mut writer = encoder.into_inner()?;
+            io::Write::flush(&mut writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FrameRecorder {
+    fn drop(&mut self) {
+        let _ = self.finish_game();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::*;
+    use crate::render::draw;
+
+    #[test]
+    fn test_records_every_nth_event() {
+        let dir = std::env::temp_dir().join(format!("minesweeper_frames_{}", std::process::id()));
+        let mut game_state: GameState = "3 1 1\n*##".parse().unwrap();
+        let mut recorder =
+            FrameRecorder::new(dir.clone(), FrameFormat::Gif, 2, TileRenderer::new(4)).unwrap();
+        let mut pixels = vec![0; 3];
+        let mut captured = 0;
+        for event in [
+            Event::Click { pos: (2, 0) },
+            Event::Click { pos: (1, 0) },
+            Event::Flag { pos: (0, 0) },
+        ] {
+            game_state.apply(event);
+            if recorder.tick() {
+                draw(&game_state, &mut pixels);
+                recorder.capture(&game_state, &pixels).unwrap();
+                captured += 1;
+            }
+        }
+        assert_eq!(captured, 1);
+        recorder.finish_game().unwrap();
+
+        let file = File::open(dir.join("game_00000.gif")).unwrap();
+        let mut decoder = gif::DecodeOptions::new().read_info(file).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (12, 4));
+        let mut frames = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 1);
+        assert_eq!("ppm".parse(), Ok(FrameFormat::Images(ImageFormat::Ppm)));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
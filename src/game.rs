@@ -1,38 +1,522 @@
-use rand::prelude::*;
+use std::{error::Error, fmt, str::FromStr};
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+use rand::{prelude::*, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Event {
     Click { pos: (usize, usize) },
     Flag { pos: (usize, usize) },
+    Unflag { pos: (usize, usize) },
+    // reveal every unflagged neighbor of a revealed cell whose mines have all been flagged.
+    Chord { pos: (usize, usize) },
     None,
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum CellState {
     Empty,
-    Mine,
+    Mine(usize), // number of mines in the cell, at most the board's `mines_per_cell`.
+}
+
+impl CellState {
+    pub fn mines(self) -> usize {
+        match self {
+            CellState::Empty => 0,
+            CellState::Mine(mines) => mines,
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum CellVisibility {
     Unknown,
-    Flagged,
-    Empty(usize), // number of neighbors that are mines.
+    Flagged(usize), // number of flags placed on the cell.
+    Empty(usize),   // number of mines in the neighboring cells.
 }
 
-#[derive(Copy, Clone, Debug)]
+impl CellVisibility {
+    pub fn flags(self) -> usize {
+        match self {
+            CellVisibility::Flagged(flags) => flags,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Cell {
     pub state: CellState,
     pub visibility: CellVisibility,
 }
 
-#[derive(Copy, Clone, PartialEq)]
+impl Cell {
+    // flags that are actually covering a mine. extra flags on a cell don't count towards winning.
+    pub fn correct_flags(&self) -> usize {
+        self.visibility.flags().min(self.state.mines())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum GameCondition {
     InProgress,
     Won,
     Lost,
 }
 
+// which edges of the board wrap around to the opposite edge. a board that wraps on both axes is a
+// torus, where every cell has the same number of neighbors.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Topology {
+    pub wrap_x: bool,
+    pub wrap_y: bool,
+}
+
+impl Topology {
+    pub const BOUNDED: Topology = Topology {
+        wrap_x: false,
+        wrap_y: false,
+    };
+    pub const TORUS: Topology = Topology {
+        wrap_x: true,
+        wrap_y: true,
+    };
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (wrap_x, wrap_y) = match s {
+            "bounded" => (false, false),
+            "wrap-x" => (true, false),
+            "wrap-y" => (false, true),
+            "torus" => (true, true),
+            _ => {
+                return Err(format!(
+                    "unknown topology {:?}, expected bounded, wrap-x, wrap-y or torus",
+                    s
+                ))
+            }
+        };
+        Ok(Topology { wrap_x, wrap_y })
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match (self.wrap_x, self.wrap_y) {
+            (false, false) => "bounded",
+            (true, false) => "wrap-x",
+            (false, true) => "wrap-y",
+            (true, true) => "torus",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// the standard board sizes. `Custom` leaves the size and the mine count up to the player.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Difficulty {
+    Beginner,
+    Intermediate,
+    Expert,
+    #[default]
+    Custom,
+}
+
+impl Difficulty {
+    // width, height and mine count of a preset.
+    pub fn board(self) -> Option<(usize, usize, usize)> {
+        match self {
+            Difficulty::Beginner => Some((9, 9, 10)),
+            Difficulty::Intermediate => Some((16, 16, 40)),
+            Difficulty::Expert => Some((30, 16, 99)),
+            Difficulty::Custom => None,
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "beginner" => Ok(Difficulty::Beginner),
+            "intermediate" => Ok(Difficulty::Intermediate),
+            "expert" => Ok(Difficulty::Expert),
+            "custom" => Ok(Difficulty::Custom),
+            _ => Err(format!(
+                "unknown difficulty {:?}, expected beginner, intermediate, expert or custom",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Difficulty::Beginner => "beginner",
+            Difficulty::Intermediate => "intermediate",
+            Difficulty::Expert => "expert",
+            Difficulty::Custom => "custom",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// the most neighbors a cell can have, since the board formats can't show higher numbers.
+pub const MAX_NEIGHBORS: usize = 34;
+
+// which cells count as the neighbors of a cell, as offsets from it. revealed numbers, flood fill
+// and the solver all go through `GameState::neighbors`, so they work the same on every neighborhood.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Neighborhood {
+    // the 8 surrounding cells of a square grid.
+    #[default]
+    Moore,
+    // the 6 surrounding cells of a hex grid. rows are laid out "odd-r", with odd rows shifted right by
+    // half a cell, so the offsets depend on the row. wrapping y needs an even height to line up, which
+    // `check_topology` enforces.
+    Hex,
+    // any other set of offsets on a square grid, e.g. `Neighborhood::knight()`. custom kernels should
+    // be built with `Neighborhood::kernel`, which checks them.
+    Kernel(Vec<(isize, isize)>),
+}
+const MOORE_OFFSETS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+const HEX_EVEN_ROW_OFFSETS: [(isize, isize); 6] =
+    [(-1, -1), (0, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)];
+const HEX_ODD_ROW_OFFSETS: [(isize, isize); 6] =
+    [(0, -1), (1, -1), (-1, 0), (1, 0), (0, 1), (1, 1)];
+
+impl Neighborhood {
+    pub fn kernel(offsets: Vec<(isize, isize)>) -> Result<Self, String> {
+        let neighborhood = Neighborhood::Kernel(offsets);
+        neighborhood.check()?;
+        Ok(neighborhood)
+    }
+
+    // the 4 cells that share an edge with a cell.
+    pub fn von_neumann() -> Self {
+        Neighborhood::Kernel(vec![(-1, 0), (0, -1), (0, 1), (1, 0)])
+    }
+
+    // the 8 cells a chess knight can jump to.
+    pub fn knight() -> Self {
+        Neighborhood::Kernel(vec![
+            (-2, -1),
+            (-2, 1),
+            (-1, -2),
+            (-1, 2),
+            (1, -2),
+            (1, 2),
+            (2, -1),
+            (2, 1),
+        ])
+    }
+
+    // every cell at most `radius` steps away, diagonals included. a radius of 1 is the moore neighborhood.
+    pub fn radius(radius: usize) -> Self {
+        let radius = radius as isize;
+        let mut offsets = Vec::new();
+        for x_offset in -radius..=radius {
+            for y_offset in -radius..=radius {
+                if (x_offset, y_offset) != (0, 0) {
+                    offsets.push((x_offset, y_offset));
+                }
+            }
+        }
+        Neighborhood::Kernel(offsets)
+    }
+
+    // kernels have to be symmetric, since the solver and the guesser find the numbers that count a
+    // cell by looking at that cell's own neighbors. offsets are limited to what the binary format stores.
+    pub fn check(&self) -> Result<(), String> {
+        let offsets = match self {
+            Neighborhood::Kernel(offsets) => offsets,
+            _ => return Ok(()),
+        };
+        if offsets.is_empty() || offsets.len() > MAX_NEIGHBORS {
+            return Err(format!(
+                "a kernel needs between 1 and {} offsets, not {}",
+                MAX_NEIGHBORS,
+                offsets.len()
+            ));
+        }
+        for (i, &(x_offset, y_offset)) in offsets.iter().enumerate() {
+            if (x_offset, y_offset) == (0, 0) {
+                return Err(String::from("a cell can't be its own neighbor"));
+            }
+            if x_offset.abs() > i8::MAX as isize || y_offset.abs() > i8::MAX as isize {
+                return Err(format!(
+                    "offset ({}, {}) is more than {} cells away",
+                    x_offset,
+                    y_offset,
+                    i8::MAX
+                ));
+            }
+            if offsets[..i].contains(&(x_offset, y_offset)) {
+                return Err(format!("offset ({}, {}) is repeated", x_offset, y_offset));
+            }
+            if !offsets.contains(&(-x_offset, -y_offset)) {
+                return Err(format!(
+                    "the kernel has ({}, {}) but not ({}, {})",
+                    x_offset, y_offset, -x_offset, -y_offset
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // hex rows alternate between two sets of offsets, so wrapping from the last row of a layer back to
+    // its first only lines up when the layer has an even number of rows. otherwise neighbors stop
+    // being mutual.
+    pub fn check_topology(&self, topology: Topology, layer_height: usize) -> Result<(), String> {
+        if *self == Neighborhood::Hex && topology.wrap_y && !layer_height.is_multiple_of(2) {
+            return Err(format!(
+                "hex boards that wrap y need an even number of rows per layer, not {}",
+                layer_height
+            ));
+        }
+        Ok(())
+    }
+
+    // the most neighbors a cell can have on a board with `depth` layers.
+    pub fn reach(&self, depth: usize) -> usize {
+        let planar = match self {
+            Neighborhood::Moore => MOORE_OFFSETS.len(),
+            Neighborhood::Hex => HEX_EVEN_ROW_OFFSETS.len(),
+            Neighborhood::Kernel(offsets) => offsets.len(),
+        };
+        if depth > 1 {
+            planar + 2 * (planar + 1)
+        } else {
+            planar
+        }
+    }
+
+    // offsets of the neighbors of a cell in row `y`.
+    pub fn offsets(&self, y: usize) -> &[(isize, isize)] {
+        match self {
+            Neighborhood::Moore => &MOORE_OFFSETS,
+            Neighborhood::Hex if y.is_multiple_of(2) => &HEX_EVEN_ROW_OFFSETS,
+            Neighborhood::Hex => &HEX_ODD_ROW_OFFSETS,
+            Neighborhood::Kernel(offsets) => offsets,
+        }
+    }
+}
+
+impl FromStr for Neighborhood {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let neighborhood = match s {
+            "moore" => Neighborhood::Moore,
+            "hex" => Neighborhood::Hex,
+            "von-neumann" => Neighborhood::von_neumann(),
+            "knight" => Neighborhood::knight(),
+            _ => {
+                if let Some(radius) = s.strip_prefix("radius-") {
+                    Neighborhood::radius(
+                        radius
+                            .parse()
+                            .map_err(|_| format!("invalid radius {:?}", radius))?,
+                    )
+                } else if let Some(offsets) = s.strip_prefix("kernel:") {
+                    // `kernel:<dx>,<dy>;<dx>,<dy>;...`
+                    let offsets = offsets
+                        .split(';')
+                        .map(|offset| match offset.split(',').collect::<Vec<_>>()[..] {
+                            [x_offset, y_offset] => {
+                                Some((x_offset.parse().ok()?, y_offset.parse().ok()?))
+                            }
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| format!("invalid kernel {:?}", s))?;
+                    Neighborhood::Kernel(offsets)
+                } else {
+                    return Err(format!(
+                        "unknown neighborhood {:?}, expected moore, hex, von-neumann, knight, radius-<n> or kernel:<dx>,<dy>;...",
+                        s
+                    ));
+                }
+            }
+        };
+        neighborhood.check()?;
+        Ok(neighborhood)
+    }
+}
+
+// kernels that match a preset are written by its name.
+impl fmt::Display for Neighborhood {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offsets = match self {
+            Neighborhood::Moore => return write!(f, "moore"),
+            Neighborhood::Hex => return write!(f, "hex"),
+            Neighborhood::Kernel(offsets) => offsets,
+        };
+        let radius = offsets
+            .iter()
+            .map(|(x, y)| x.unsigned_abs().max(y.unsigned_abs()))
+            .max()
+            .unwrap_or(0);
+        if *self == Neighborhood::von_neumann() {
+            write!(f, "von-neumann")
+        } else if *self == Neighborhood::knight() {
+            write!(f, "knight")
+        } else if *self == Neighborhood::radius(radius) {
+            write!(f, "radius-{}", radius)
+        } else {
+            write!(f, "kernel:")?;
+            for (i, (x_offset, y_offset)) in offsets.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ";")?;
+                }
+                write!(f, "{},{}", x_offset, y_offset)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// `coordinate + offset` on an axis of length `len`, wrapping around if `wrap` is set.
+fn offset_coordinate(coordinate: usize, offset: isize, len: usize, wrap: bool) -> Option<usize> {
+    let moved = coordinate as isize + offset;
+    if wrap {
+        Some(moved.rem_euclid(len as isize) as usize)
+    } else if moved < 0 || moved >= len as isize {
+        None
+    } else {
+        Some(moved as usize)
+    }
+}
+
+// what a single move did to the board, so that callers don't need to rescan the field after it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveOutcome {
+    pub event: Event,
+    // cells that the move revealed, with their numbers, in the order they were revealed.
+    pub revealed: Vec<((usize, usize), usize)>,
+    // cells whose flags were added or removed.
+    pub flagged: Vec<(usize, usize)>,
+    pub hit_mine: bool,
+    // the game condition before and after the move.
+    pub condition: (GameCondition, GameCondition),
+}
+
+impl MoveOutcome {
+    // whether the move changed anything at all.
+    pub fn is_empty(&self) -> bool {
+        self.revealed.is_empty() && self.flagged.is_empty() && !self.ended_game()
+    }
+
+    pub fn ended_game(&self) -> bool {
+        self.condition.0 == GameCondition::InProgress
+            && self.condition.1 != GameCondition::InProgress
+    }
+
+    // every cell that the move changed.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.revealed
+            .iter()
+            .map(|(pos, _)| *pos)
+            .chain(self.flagged.iter().copied())
+    }
+}
+
+// why a board couldn't be made, or a move couldn't be played on it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GameError {
+    InvalidDimensions {
+        width: usize,
+        height: usize,
+    },
+    // every cell would be full of mines, so mine placement would never finish.
+    TooManyMines {
+        mines: usize,
+        capacity: usize,
+    },
+    OutOfBounds {
+        pos: (usize, usize),
+    },
+    GameOver,
+    IllegalMove {
+        event: Event,
+        reason: &'static str,
+    },
+    MismatchedBoards {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::InvalidDimensions { width, height } if *width == 0 || *height == 0 => {
+                write!(f, "a {}x{} board has no cells", width, height)
+            }
+            GameError::InvalidDimensions { width, height } => {
+                write!(f, "a {}x{} board has too many cells", width, height)
+            }
+            GameError::TooManyMines { mines, capacity } => write!(
+                f,
+                "{} mines leave no room to spare on a board that holds {}",
+                mines, capacity
+            ),
+            GameError::OutOfBounds { pos } => {
+                write!(f, "({}, {}) is outside of the board", pos.0, pos.1)
+            }
+            GameError::GameOver => write!(f, "the game is already over"),
+            GameError::IllegalMove { event, reason } => {
+                write!(f, "illegal move {:?}: {}", event, reason)
+            }
+            GameError::MismatchedBoards { expected, found } => write!(
+                f,
+                "expected a {}x{} board, found {}x{}",
+                expected.0, expected.1, found.0, found.1
+            ),
+        }
+    }
+}
+
+impl Error for GameError {}
+
+#[derive(Copy, Clone, Debug)]
+struct CellChange {
+    index: usize,
+    before: Cell,
+    after: Cell,
+}
+
+// the cells changed by a single event, so that it can be undone and redone without cloning the field.
+#[derive(Clone, Debug)]
+struct HistoryEntry {
+    event: Event,
+    changes: Vec<CellChange>,
+    game_condition: (GameCondition, GameCondition),
+    flagged_count: (usize, usize),
+    hidden_count: (usize, usize),
+}
+
+#[derive(Clone, Default)]
+struct History {
+    done: Vec<HistoryEntry>,
+    undone: Vec<HistoryEntry>,
+}
+
 #[derive(Clone)]
 pub struct GameState {
     pub field: Vec<Cell>,
@@ -41,10 +525,97 @@ pub struct GameState {
     pub game_condition: GameCondition,
     pub bomb_count: usize,
     flagged_count: usize,
+    // cells that haven't been revealed yet, flagged or not.
+    hidden_count: usize,
+    history: History,
+    // whether clicking a cell without neighboring mines also clicks all of its neighbors.
+    pub flood_fill: bool,
+    pub topology: Topology,
+    pub neighborhood: Neighborhood,
+    // the most mines a single cell can hold. a cell with several mines adds all of them to the
+    // numbers around it and needs one flag per mine.
+    pub mines_per_cell: usize,
+    // 3d boards stack their layers vertically, so `height` counts the rows of every layer and row `y`
+    // is on layer `y / layer_height()`.
+    pub depth: usize,
 }
 
 impl GameState {
     pub fn new(width: usize, height: usize, num_bombs: usize) -> Self {
+        GameState::with_rng(width, height, num_bombs, &mut thread_rng())
+    }
+
+    pub fn from_seed(width: usize, height: usize, num_bombs: usize, seed: u64) -> Self {
+        GameState::with_rng(width, height, num_bombs, &mut StdRng::seed_from_u64(seed))
+    }
+
+    // like `new`, but reports boards that can't be made instead of panicking or never finishing.
+    pub fn try_new(width: usize, height: usize, num_bombs: usize) -> Result<Self, GameError> {
+        GameState::check_dimensions(width, height, num_bombs, 1)?;
+        Ok(GameState::new(width, height, num_bombs))
+    }
+
+    pub fn try_with_rng_multi_mine<R: Rng + ?Sized>(
+        width: usize,
+        height: usize,
+        num_bombs: usize,
+        mines_per_cell: usize,
+        rng: &mut R,
+    ) -> Result<Self, GameError> {
+        GameState::check_dimensions(width, height, num_bombs, mines_per_cell)?;
+        Ok(GameState::with_rng_multi_mine(
+            width,
+            height,
+            num_bombs,
+            mines_per_cell,
+            rng,
+        ))
+    }
+
+    // the board has to hold more mines than are placed, otherwise placing them never finishes. with a
+    // single mine per cell that leaves at least one safe cell.
+    pub fn check_dimensions(
+        width: usize,
+        height: usize,
+        num_bombs: usize,
+        mines_per_cell: usize,
+    ) -> Result<(), GameError> {
+        let capacity = width
+            .checked_mul(height)
+            .and_then(|cells| cells.checked_mul(mines_per_cell))
+            .filter(|_| width > 0 && height > 0)
+            .ok_or(GameError::InvalidDimensions { width, height })?;
+        if num_bombs >= capacity {
+            return Err(GameError::TooManyMines {
+                mines: num_bombs,
+                capacity,
+            });
+        }
+        Ok(())
+    }
+
+    // mine placement is driven entirely by `rng`, so a seeded rng always produces the same board.
+    pub fn with_rng<R: Rng + ?Sized>(
+        width: usize,
+        height: usize,
+        num_bombs: usize,
+        rng: &mut R,
+    ) -> Self {
+        GameState::with_rng_multi_mine(width, height, num_bombs, 1, rng)
+    }
+
+    // like `with_rng`, but a cell can hold up to `mines_per_cell` of the mines. with a single mine per
+    // cell this draws the same board as `with_rng` for the same rng.
+    pub fn with_rng_multi_mine<R: Rng + ?Sized>(
+        width: usize,
+        height: usize,
+        num_bombs: usize,
+        mines_per_cell: usize,
+        rng: &mut R,
+    ) -> Self {
+        if let Err(e) = GameState::check_dimensions(width, height, num_bombs, mines_per_cell) {
+            panic!("{}", e);
+        }
         let mut cells = vec![
             Cell {
                 state: CellState::Empty,
@@ -53,12 +624,13 @@ impl GameState {
             width * height
         ];
 
-        for bomb in 0..num_bombs {
+        for _ in 0..num_bombs {
             // note: naive mine generation can lead to unsolvable patterns.
             loop {
-                let (x, y) = GameState::random_xy(width, height);
-                if cells[y * width + x].state == CellState::Empty {
-                    cells[y * width + x].state = CellState::Mine;
+                let (x, y) = (rng.gen_range(0..width), rng.gen_range(0..height));
+                let mines = cells[y * width + x].state.mines();
+                if mines < mines_per_cell {
+                    cells[y * width + x].state = CellState::Mine(mines + 1);
                     break;
                 }
             }
@@ -69,12 +641,76 @@ impl GameState {
             game_condition: GameCondition::InProgress,
             bomb_count: num_bombs,
             flagged_count: 0,
+            hidden_count: width * height,
             width,
             height,
+            history: History::default(),
+            flood_fill: false,
+            topology: Topology::BOUNDED,
+            neighborhood: Neighborhood::Moore,
+            depth: 1,
+            mines_per_cell,
         }
     }
+    // build a game from an existing field, e.g. one that was loaded from disk.
+    // flags on mines count towards the win condition just like they do in `flag`. cells may hold as
+    // many mines as the fullest cell in the field.
+    pub fn from_field(width: usize, height: usize, field: Vec<Cell>) -> Self {
+        assert_eq!(field.len(), width * height);
+        let bomb_count = field.iter().map(|c| c.state.mines()).sum();
+        let flagged_count = field.iter().map(Cell::correct_flags).sum();
+        let mines_per_cell = field
+            .iter()
+            .map(|c| c.state.mines())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let hidden_count = field
+            .iter()
+            .filter(|c| !matches!(c.visibility, CellVisibility::Empty(_)))
+            .count();
+        let mut game_state = GameState {
+            field,
+            width,
+            height,
+            game_condition: GameCondition::InProgress,
+            bomb_count,
+            flagged_count,
+            hidden_count,
+            history: History::default(),
+            flood_fill: false,
+            topology: Topology::BOUNDED,
+            neighborhood: Neighborhood::Moore,
+            depth: 1,
+            mines_per_cell,
+        };
+        if bomb_count > 0 {
+            game_state.check_flags_won();
+        }
+        game_state.check_revealed_won();
+        game_state
+    }
+
     pub fn remaining_mines(&self) -> usize {
-        self.bomb_count - self.flagged_count
+        self.bomb_count.saturating_sub(self.flagged_count)
+    }
+
+    // every flag on the board, right or wrong. unlike `remaining_mines`, a player can work out
+    // `bomb_count - flags_placed()` from what they see.
+    pub fn flags_placed(&self) -> usize {
+        self.field.iter().map(|c| c.visibility.flags()).sum()
+    }
+
+    // the mine counter a player sees: every flag is taken to cover a mine, right or wrong.
+    pub fn mines_left(&self) -> usize {
+        self.bomb_count.saturating_sub(self.flags_placed())
+    }
+
+    // whether every cell without a mine has been revealed.
+    pub fn all_safe_revealed(&self) -> bool {
+        self.field
+            .iter()
+            .all(|c| c.state.mines() > 0 || matches!(c.visibility, CellVisibility::Empty(_)))
     }
 
     pub fn random_xy(width: usize, height: usize) -> (usize, usize) {
@@ -111,76 +747,196 @@ impl GameState {
         }
     }
 
+    // rows of one layer. 2d boards are a single layer.
+    pub fn layer_height(&self) -> usize {
+        self.height / self.depth
+    }
+
+    // one layer of a 3d board as a board of its own, e.g. for drawing it. its numbers still count
+    // the mines in the layers around it.
+    pub fn layer(&self, z: usize) -> GameState {
+        let layer_size = self.width * self.layer_height();
+        let field = self.field[z * layer_size..(z + 1) * layer_size].to_vec();
+        GameState {
+            hidden_count: field
+                .iter()
+                .filter(|c| !matches!(c.visibility, CellVisibility::Empty(_)))
+                .count(),
+            field,
+            height: self.layer_height(),
+            depth: 1,
+            history: History::default(),
+            neighborhood: self.neighborhood.clone(),
+            ..*self
+        }
+    }
+
+    // on 3d boards a cell also neighbors the cells directly above and below it, along with every cell
+    // that the neighborhood reaches from those, e.g. 26 cells for the moore neighborhood.
+    // on small wrapping boards an offset can land on the cell itself or on a neighbor that was already
+    // found from the other side, so those are skipped.
     pub fn neighbors(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let layer_height = self.layer_height();
+        let (z, row) = (y / layer_height, y % layer_height);
+        let z_offsets: &[isize] = if self.depth > 1 { &[0, -1, 1] } else { &[0] };
         let mut neighbors = Vec::new();
-        for x_offset in [-1isize, 0, 1].iter() {
-            for y_offset in [-1isize, 0, 1].iter() {
-                if *x_offset == 0 && *y_offset == 0 {
-                    continue;
-                }
-                if (x == 0 && *x_offset < 0) || (y == 0 && *y_offset < 0) {
-                    continue;
-                }
-                let (x, y) = (
-                    (x as isize + *x_offset) as usize,
-                    (y as isize + *y_offset) as usize,
-                );
-                if x >= self.width || y >= self.height {
+        for z_offset in z_offsets {
+            let z = match offset_coordinate(z, *z_offset, self.depth, false) {
+                Some(z) => z,
+                None => continue,
+            };
+            let above_or_below: &[(isize, isize)] = if *z_offset == 0 { &[] } else { &[(0, 0)] };
+            for (x_offset, y_offset) in above_or_below.iter().chain(self.neighborhood.offsets(row))
+            {
+                let neighbor = match (
+                    offset_coordinate(x, *x_offset, self.width, self.topology.wrap_x),
+                    offset_coordinate(row, *y_offset, layer_height, self.topology.wrap_y),
+                ) {
+                    (Some(x), Some(row)) => (x, z * layer_height + row),
+                    _ => continue,
+                };
+                if neighbor == (x, y) || neighbors.contains(&neighbor) {
                     continue;
                 }
-                neighbors.push((x, y));
+                neighbors.push(neighbor);
             }
         }
         neighbors
     }
 
-    pub fn flag(&mut self, x: usize, y: usize) {
+    pub fn neighboring_mines(&self, x: usize, y: usize) -> usize {
+        self.neighbors(x, y)
+            .iter()
+            .map(|(x, y)| self.at(*x, *y).unwrap().state.mines())
+            .sum()
+    }
+
+    pub fn flag(&mut self, x: usize, y: usize) -> MoveOutcome {
+        let event = Event::Flag { pos: (x, y) };
         let copy = self.at(x, y);
         if copy.is_none() {
-            return;
+            return self.unchanged(event);
         }
         let copy = copy.unwrap();
-        if copy.state == CellState::Mine && copy.visibility != CellVisibility::Flagged {
-            // if we flag an unflagged mine, advance the win condition.
-            self.flagged_count += 1;
-            if self.flagged_count == self.bomb_count {
-                self.game_condition = GameCondition::Won;
-            }
+        // a revealed cell keeps its number.
+        if let CellVisibility::Empty(_) = copy.visibility {
+            return self.unchanged(event);
         }
-        // for (nx, ny) in self.neighbors(x, y).iter() {
-        //     if let Some(Cell {
-        //         visibility: CellVisibility::Empty(neighbors),
-        //         state,
-        //     }) = self.at(*nx, *ny)
-        //     {
-        //         if neighbors >= 1 {
-        //             *self.at_mut(*nx, *ny).unwrap() = Cell {
-        //                 state,
-        //                 visibility: CellVisibility::Empty(neighbors - 1),
-        //             };
-        //         }
-        //     }
-        // }
-        let mut_cell = self.at_mut(x, y).unwrap();
-        *mut_cell = Cell {
-            visibility: CellVisibility::Flagged,
+        let (condition_before, flagged_before) = (self.game_condition, self.flagged_count);
+        // each flag covers one mine, so a cell takes at most as many flags as it can hold mines.
+        let flagged = Cell {
+            visibility: CellVisibility::Flagged(
+                (copy.visibility.flags() + 1).min(self.mines_per_cell),
+            ),
             ..copy
         };
+        // if we flag an unflagged mine, advance the win condition.
+        self.flagged_count += flagged.correct_flags() - copy.correct_flags();
+        let mut changes = Vec::new();
+        self.set_cell(x, y, flagged, &mut changes);
+        self.check_flags_won();
+        self.commit(event, changes, condition_before, flagged_before)
     }
 
-    pub fn click(&mut self, x: usize, y: usize) {
-        let mut click_queue = vec![(x, y)];
-        loop {
-            let coords = click_queue.pop();
-            if coords.is_none() {
-                break;
+    pub fn unflag(&mut self, x: usize, y: usize) -> MoveOutcome {
+        let event = Event::Unflag { pos: (x, y) };
+        let copy = match self.at(x, y) {
+            Some(copy) if copy.visibility.flags() > 0 => copy,
+            _ => return self.unchanged(event),
+        };
+        let (condition_before, flagged_before) = (self.game_condition, self.flagged_count);
+        // remove a single flag, so a cell with several flags stays flagged.
+        let unflagged = Cell {
+            visibility: match copy.visibility.flags() - 1 {
+                0 => CellVisibility::Unknown,
+                flags => CellVisibility::Flagged(flags),
+            },
+            ..copy
+        };
+        self.flagged_count -= copy.correct_flags() - unflagged.correct_flags();
+        let mut changes = Vec::new();
+        self.set_cell(x, y, unflagged, &mut changes);
+        // taking away a wrong flag can leave exactly the mines flagged.
+        self.check_flags_won();
+        self.commit(event, changes, condition_before, flagged_before)
+    }
+
+    // the game is won once every mine is flagged, as long as no flag is wrong. otherwise flagging every
+    // cell would win. the flags are only counted once all the mines are covered.
+    fn check_flags_won(&mut self) {
+        if self.game_condition == GameCondition::InProgress
+            && self.flagged_count == self.bomb_count
+            && self.flags_placed() == self.bomb_count
+        {
+            self.game_condition = GameCondition::Won;
+        }
+    }
+
+    // the game is also won by revealing every safe cell, without flagging any mine. mines are never
+    // revealed, so this can only happen once no more cells are hidden than there are mines.
+    fn check_revealed_won(&mut self) {
+        if self.game_condition == GameCondition::InProgress
+            && self.hidden_count <= self.bomb_count
+            && self.all_safe_revealed()
+        {
+            self.game_condition = GameCondition::Won;
+        }
+    }
+
+    pub fn click(&mut self, x: usize, y: usize) -> MoveOutcome {
+        let (condition_before, flagged_before) = (self.game_condition, self.flagged_count);
+        let mut changes = Vec::new();
+        self.reveal(x, y, &mut changes);
+        self.check_revealed_won();
+        self.commit(
+            Event::Click { pos: (x, y) },
+            changes,
+            condition_before,
+            flagged_before,
+        )
+    }
+
+    pub fn chord(&mut self, x: usize, y: usize) -> MoveOutcome {
+        let event = Event::Chord { pos: (x, y) };
+        let mines = match self.at(x, y) {
+            Some(Cell {
+                visibility: CellVisibility::Empty(mines),
+                ..
+            }) => mines,
+            _ => return self.unchanged(event),
+        };
+        let neighbors = self.neighbors(x, y);
+        let flagged: usize = neighbors
+            .iter()
+            .map(|(x, y)| self.at(*x, *y).unwrap().visibility.flags())
+            .sum();
+        if flagged != mines {
+            return self.unchanged(event);
+        }
+        let (condition_before, flagged_before) = (self.game_condition, self.flagged_count);
+        let mut changes = Vec::new();
+        for (nx, ny) in neighbors {
+            if self.at(nx, ny).unwrap().visibility == CellVisibility::Unknown {
+                self.reveal(nx, ny, &mut changes);
             }
-            let (x, y) = coords.unwrap();
+        }
+        self.check_revealed_won();
+        self.commit(event, changes, condition_before, flagged_before)
+    }
+
+    fn reveal(&mut self, x: usize, y: usize, changes: &mut Vec<CellChange>) {
+        let mut click_queue = vec![(x, y)];
+        while let Some((x, y)) = click_queue.pop() {
             if let Some(copy) = self.at(x, y) {
                 let mut click_neighbors = false;
-                *self.at_mut(x, y).unwrap() = match copy {
+                let cell = match copy {
+                    // flags protect their cell from clicks, whatever is under them.
                     Cell {
-                        state: CellState::Mine,
+                        visibility: CellVisibility::Flagged(_),
+                        ..
+                    } => copy,
+                    Cell {
+                        state: CellState::Mine(_),
                         ..
                     } => {
                         self.game_condition = GameCondition::Lost;
@@ -191,25 +947,12 @@ impl GameState {
                         visibility: CellVisibility::Unknown,
                     } => {
                         // calculate neighbors
-                        let mine_count = self
-                            .neighbors(x, y)
-                            .iter()
-                            .map(|(x, y)| {
-                                if let Some(cell) = self.at(*x, *y) {
-                                    if cell.state == CellState::Mine {
-                                        1usize
-                                    } else {
-                                        0
-                                    }
-                                } else {
-                                    0
-                                }
-                            })
-                            .sum::<usize>();
-
-                        if mine_count == 0 {
-                            // click_neighbors = true;
+                        let mine_count = self.neighboring_mines(x, y);
+
+                        if mine_count == 0 && self.flood_fill {
+                            click_neighbors = true;
                         }
+                        self.hidden_count -= 1;
 
                         Cell {
                             visibility: CellVisibility::Empty(mine_count),
@@ -218,19 +961,13 @@ impl GameState {
                     }
                     _ => copy,
                 };
+                self.set_cell(x, y, cell, changes);
                 if click_neighbors {
-                    for x_offset in [-1isize, 0, 1].iter() {
-                        for y_offset in [-1isize, 0, 1].iter() {
-                            if *x_offset == 0 && *y_offset == 0 {
-                                continue;
-                            }
-                            if (x == 0 && *x_offset < 0) || (y == 0 && *y_offset < 0) {
-                                continue;
-                            }
-                            click_queue.push((
-                                (x as isize + x_offset) as usize,
-                                (y as isize + y_offset) as usize,
-                            ));
+                    for neighbor in self.neighbors(x, y) {
+                        if self.at(neighbor.0, neighbor.1).unwrap().visibility
+                            == CellVisibility::Unknown
+                        {
+                            click_queue.push(neighbor);
                         }
                     }
                 }
@@ -238,29 +975,501 @@ impl GameState {
         }
     }
 
-    pub fn validate(&self, hypothetical: &GameState) -> bool {
+    fn set_cell(&mut self, x: usize, y: usize, cell: Cell, changes: &mut Vec<CellChange>) {
+        let index = y * self.width + x;
+        let before = self.field[index];
+        if before != cell {
+            changes.push(CellChange {
+                index,
+                before,
+                after: cell,
+            });
+            self.field[index] = cell;
+        }
+    }
+
+    // record a move in the history, and report what it did. a new move discards anything that could
+    // have been redone.
+    fn commit(
+        &mut self,
+        event: Event,
+        changes: Vec<CellChange>,
+        condition_before: GameCondition,
+        flagged_before: usize,
+    ) -> MoveOutcome {
+        let mut outcome = self.unchanged(event);
+        outcome.condition.0 = condition_before;
+        outcome.hit_mine =
+            condition_before != GameCondition::Lost && self.game_condition == GameCondition::Lost;
+        for change in changes.iter() {
+            let pos = (change.index % self.width, change.index / self.width);
+            match (change.before.visibility, change.after.visibility) {
+                (CellVisibility::Empty(_), _) => {}
+                (_, CellVisibility::Empty(n)) => outcome.revealed.push((pos, n)),
+                (before, after) if before.flags() != after.flags() => outcome.flagged.push(pos),
+                _ => {}
+            }
+        }
+        if changes.is_empty() && condition_before == self.game_condition {
+            return outcome;
+        }
+        // moves only ever reveal cells, never hide them again.
+        let hidden_before = self.hidden_count + outcome.revealed.len();
+        self.history.undone.clear();
+        self.history.done.push(HistoryEntry {
+            event,
+            changes,
+            game_condition: (condition_before, self.game_condition),
+            flagged_count: (flagged_before, self.flagged_count),
+            hidden_count: (hidden_before, self.hidden_count),
+        });
+        outcome
+    }
+
+    // the outcome of a move that didn't change anything.
+    fn unchanged(&self, event: Event) -> MoveOutcome {
+        MoveOutcome {
+            event,
+            revealed: Vec::new(),
+            flagged: Vec::new(),
+            hit_mine: false,
+            condition: (self.game_condition, self.game_condition),
+        }
+    }
+
+    // revert the last move, returning the event that caused it.
+    pub fn undo(&mut self) -> Option<Event> {
+        let entry = self.history.done.pop()?;
+        for change in entry.changes.iter().rev() {
+            self.field[change.index] = change.before;
+        }
+        self.game_condition = entry.game_condition.0;
+        self.flagged_count = entry.flagged_count.0;
+        self.hidden_count = entry.hidden_count.0;
+        let event = entry.event;
+        self.history.undone.push(entry);
+        Some(event)
+    }
+
+    // reapply the last undone move, returning the event that caused it.
+    pub fn redo(&mut self) -> Option<Event> {
+        let entry = self.history.undone.pop()?;
+        for change in entry.changes.iter() {
+            self.field[change.index] = change.after;
+        }
+        self.game_condition = entry.game_condition.1;
+        self.flagged_count = entry.flagged_count.1;
+        self.hidden_count = entry.hidden_count.1;
+        let event = entry.event;
+        self.history.done.push(entry);
+        Some(event)
+    }
+
+    // the events that can currently be undone, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = Event> + '_ {
+        self.history.done.iter().map(|e| e.event)
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.done.len()
+    }
+
+    // a copy of the board without its undo history, for throwaway hypotheticals.
+    pub fn snapshot(&self) -> GameState {
+        GameState {
+            field: self.field.clone(),
+            history: History::default(),
+            neighborhood: self.neighborhood.clone(),
+            ..*self
+        }
+    }
+
+    pub fn apply(&mut self, event: Event) -> MoveOutcome {
+        match event {
+            Event::Flag { pos } => self.flag(pos.0, pos.1),
+            Event::Click { pos } => self.click(pos.0, pos.1),
+            Event::Unflag { pos } => self.unflag(pos.0, pos.1),
+            Event::Chord { pos } => self.chord(pos.0, pos.1),
+            Event::None => self.unchanged(event),
+        }
+    }
+
+    // why `event` can't be made in the current position, if it can't.
+    pub fn check_event(&self, event: Event) -> Result<(), GameError> {
+        let pos = match event {
+            Event::Click { pos }
+            | Event::Flag { pos }
+            | Event::Unflag { pos }
+            | Event::Chord { pos } => pos,
+            Event::None => return Ok(()),
+        };
+        let cell = match self.at(pos.0, pos.1) {
+            Some(cell) => cell,
+            None => return Err(GameError::OutOfBounds { pos }),
+        };
+        if self.game_condition != GameCondition::InProgress {
+            return Err(GameError::GameOver);
+        }
+        let reason = match (event, cell.visibility) {
+            (Event::Click { .. }, CellVisibility::Flagged(_)) => "the cell is flagged",
+            (Event::Click { .. }, CellVisibility::Empty(_)) => "the cell is already revealed",
+            (Event::Flag { .. }, CellVisibility::Flagged(flags))
+                if flags >= self.mines_per_cell =>
+            {
+                "the cell is already flagged"
+            }
+            (Event::Flag { .. }, CellVisibility::Empty(_)) => "the cell is revealed",
+            (Event::Unflag { .. }, CellVisibility::Unknown)
+            | (Event::Unflag { .. }, CellVisibility::Empty(_)) => "the cell is not flagged",
+            (Event::Chord { .. }, CellVisibility::Unknown)
+            | (Event::Chord { .. }, CellVisibility::Flagged(_)) => "the cell is not revealed",
+            _ => return Ok(()),
+        };
+        Err(GameError::IllegalMove { event, reason })
+    }
+
+    pub fn try_apply(&mut self, event: Event) -> Result<MoveOutcome, GameError> {
+        self.check_event(event)?;
+        Ok(self.apply(event))
+    }
+
+    pub fn try_click(&mut self, x: usize, y: usize) -> Result<MoveOutcome, GameError> {
+        self.try_apply(Event::Click { pos: (x, y) })
+    }
+
+    pub fn try_flag(&mut self, x: usize, y: usize) -> Result<MoveOutcome, GameError> {
+        self.try_apply(Event::Flag { pos: (x, y) })
+    }
+
+    // like `validate`, but boards of different sizes are an error rather than compared cell by cell.
+    pub fn try_validate(&self, hypothetical: &GameState) -> Result<bool, GameError> {
+        let expected = (self.width, self.height);
+        let found = (hypothetical.width, hypothetical.height);
+        if expected != found {
+            return Err(GameError::MismatchedBoards { expected, found });
+        }
         // returns whether the hypothetical gamestate is the same as the current gamestate
         // after visibility is factored in.
-
-        for (i, (cell1, cell2)) in self.field.iter().zip(hypothetical.field.iter()).enumerate() {
+        for (i, cell1) in self.field.iter().enumerate() {
             if let CellVisibility::Empty(n1) = cell1.visibility {
                 let (x, y) = (i % self.width, i / self.width);
-                let n2 = hypothetical
-                    .neighbors(x, y)
-                    .iter()
-                    .map(|e| {
-                        if hypothetical.at(e.0, e.1).unwrap().state == CellState::Mine {
-                            1usize
-                        } else {
-                            0usize
-                        }
-                    })
-                    .sum();
-                if n1 != n2 {
-                    return false;
+                if n1 != hypothetical.neighboring_mines(x, y) {
+                    return Ok(false);
                 }
             }
         }
-        true
+        Ok(true)
+    }
+
+    // a hypothetical of a different size never matches.
+    pub fn validate(&self, hypothetical: &GameState) -> bool {
+        self.try_validate(hypothetical).unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_undo_redo() {
+        let mut game_state: GameState = "3 2 1\n*##\n###".parse().unwrap();
+        let start = game_state.to_string();
+        game_state.click(2, 0);
+        game_state.flag(0, 0);
+        let won = game_state.to_string();
+        assert_eq!(game_state.game_condition, GameCondition::Won);
+
+        assert_eq!(game_state.undo(), Some(Event::Flag { pos: (0, 0) }));
+        assert_eq!(game_state.game_condition, GameCondition::InProgress);
+        assert_eq!(game_state.remaining_mines(), 1);
+        assert_eq!(game_state.undo(), Some(Event::Click { pos: (2, 0) }));
+        assert_eq!(game_state.undo(), None);
+        assert_eq!(game_state.to_string(), start);
+
+        assert_eq!(game_state.redo(), Some(Event::Click { pos: (2, 0) }));
+        assert_eq!(game_state.redo(), Some(Event::Flag { pos: (0, 0) }));
+        assert_eq!(game_state.to_string(), won);
+
+        game_state.undo();
+        game_state.unflag(0, 0);
+        assert_eq!(game_state.history_len(), 1);
+        game_state.flag(0, 0);
+        game_state.unflag(0, 0);
+        assert_eq!(game_state.remaining_mines(), 1);
+        game_state.undo();
+        game_state.undo();
+        game_state.redo();
+
+        // a new move after an undo discards the redo stack.
+        game_state.undo();
+        game_state.click(1, 1);
+        assert_eq!(game_state.redo(), None);
+        assert_eq!(game_state.history_len(), 2);
+    }
+
+    #[test]
+    fn test_flood_fill_and_chord() {
+        let mut game_state: GameState = "4 3 1\n*###\n####\n####".parse().unwrap();
+        game_state.flood_fill = true;
+        game_state.click(3, 2);
+        assert_eq!(game_state.to_string(), "4 3 1\n*1..\n11..\n....\n");
+        assert_eq!(game_state.history_len(), 1);
+
+        let mut game_state: GameState = "3 2 1\n*##\n###".parse().unwrap();
+        game_state.click(1, 1);
+        game_state.chord(1, 1);
+        assert_eq!(game_state.history_len(), 1);
+        game_state.flag(0, 0);
+        game_state.chord(1, 1);
+        assert_eq!(game_state.to_string(), "3 2 1\nF1.\n11.\n");
+        game_state.undo();
+        assert_eq!(game_state.to_string(), "3 2 1\nF##\n#1#\n");
+    }
+
+    #[test]
+    fn test_wrapping_topology() {
+        let mut game_state = GameState::from_seed(4, 3, 0, 0);
+        assert_eq!(game_state.neighbors(0, 0).len(), 3);
+        game_state.topology = Topology::TORUS;
+        assert_eq!(game_state.neighbors(0, 0).len(), 8);
+        assert!(game_state.neighbors(0, 0).contains(&(3, 2)));
+        game_state.topology = "wrap-y".parse().unwrap();
+        assert_eq!(game_state.neighbors(0, 0).len(), 5);
+        // on a 2x2 torus every other cell is a neighbor, but only once.
+        assert_eq!(GameState::from_seed(2, 2, 0, 0).neighbors(0, 0).len(), 3);
+
+        // the right edge touches the flagged mine, so this is only consistent when x wraps.
+        assert!("4 1 1\nF1#1".parse::<GameState>().is_err());
+        let mut game_state: GameState = "4 1 1 wrap-x\nF1#1".parse().unwrap();
+        assert!(crate::solver::Solver::deductions(&game_state)
+            .safe
+            .contains(&(2, 0)));
+        game_state.flood_fill = true;
+        game_state.click(2, 0);
+        assert_eq!(game_state.to_string(), "4 1 1 wrap-x\nF1.1\n");
+    }
+
+    #[test]
+    fn test_hex_neighborhood() {
+        let mut game_state = GameState::from_seed(3, 3, 0, 0);
+        game_state.neighborhood = Neighborhood::Hex;
+        let mut neighbors = game_state.neighbors(1, 1);
+        neighbors.sort();
+        assert_eq!(
+            neighbors,
+            vec![(0, 1), (1, 0), (1, 2), (2, 0), (2, 1), (2, 2)]
+        );
+        assert_eq!(game_state.neighbors(1, 0).len(), 4);
+
+        // (0, 0) doesn't touch (1, 1) on a hex grid, so the mine has to be there.
+        assert!("2 2 1\n.1\n#*".parse::<GameState>().is_err());
+        let game_state: GameState = "2 2 1 hex\n.1\n#*".parse().unwrap();
+        let deductions = crate::solver::Solver::deductions(&game_state);
+        assert!(deductions.safe.contains(&(0, 1)));
+        assert!(deductions.mines.contains(&(1, 1)));
+    }
+
+    #[test]
+    fn test_kernels() {
+        for name in [
+            "moore",
+            "hex",
+            "von-neumann",
+            "knight",
+            "radius-2",
+            "kernel:2,0;-2,0",
+        ]
+        .iter()
+        {
+            assert_eq!(name.parse::<Neighborhood>().unwrap().to_string(), *name);
+        }
+        assert!("kernel:1,0".parse::<Neighborhood>().is_err());
+        assert!("kernel:0,0".parse::<Neighborhood>().is_err());
+        // 48 neighbors are more than the board formats can show.
+        assert!("radius-3".parse::<Neighborhood>().is_err());
+
+        // 12 mines within two cells of the center.
+        let text = "5 5 12 radius-2\n*****\n*****\n**c##\n#####\n#####\n";
+        let game_state: GameState = text.parse().unwrap();
+        assert_eq!(
+            game_state.at(2, 2).unwrap().visibility,
+            CellVisibility::Empty(12)
+        );
+        assert_eq!(game_state.to_string(), text);
+
+        // a knight's move from the blank corner proves (2, 1) safe, which leaves the mine for the 1s.
+        assert!("3 3 1\n1#1\n###\n.*#".parse::<GameState>().is_err());
+        let game_state: GameState = "3 3 1 knight\n1#1\n###\n.*#".parse().unwrap();
+        let deductions = crate::solver::Solver::deductions(&game_state);
+        assert!(deductions.safe.contains(&(2, 1)));
+        assert!(deductions.mines.contains(&(1, 2)));
+    }
+
+    #[test]
+    fn test_3d_board() {
+        let mut game_state = GameState::from_seed(3, 9, 0, 0);
+        game_state.depth = 3;
+        // the center of a 3x3x3 cube, in the middle of the second layer.
+        assert_eq!(game_state.neighbors(1, 4).len(), 26);
+        assert_eq!(game_state.neighbors(0, 0).len(), 7);
+        // the last row of a layer doesn't touch the first row of the next one.
+        assert!(!game_state.neighbors(0, 2).contains(&(0, 3)));
+        game_state.neighborhood = Neighborhood::von_neumann();
+        assert_eq!(game_state.neighbors(1, 4).len(), 14);
+
+        // the 1s in the first layer can only be counting the mine in the second.
+        let text = "3 2 1 depth-2\n.11\n\n##*\n";
+        let game_state: GameState = text.parse().unwrap();
+        assert_eq!(game_state.to_string(), text);
+        assert_eq!(game_state.layer(1).to_string(), "3 1 1\n##*\n");
+        let deductions = crate::solver::Solver::deductions(&game_state);
+        assert!(deductions.safe.contains(&(0, 1)) && deductions.safe.contains(&(1, 1)));
+        assert!(deductions.mines.contains(&(2, 1)));
+    }
+
+    #[test]
+    fn test_multi_mine_cells() {
+        // the 3 counts both mines in the first cell and the one in the last.
+        let mut game_state: GameState = "3 1 3 mines-per-cell-2\n{**}3*\n".parse().unwrap();
+        assert_eq!(game_state.at(0, 0).unwrap().state, CellState::Mine(2));
+        assert_eq!(game_state.neighboring_mines(1, 0), 3);
+        assert!("3 1 3\n{**}3*".parse::<GameState>().is_err());
+
+        // a cell takes one flag per mine it can hold.
+        game_state.flag(0, 0);
+        assert_eq!(game_state.to_string(), "3 1 3 mines-per-cell-2\n{F*}3*\n");
+        assert_eq!(game_state.remaining_mines(), 2);
+        game_state.flag(0, 0);
+        game_state.flag(0, 0);
+        assert_eq!(
+            game_state.at(0, 0).unwrap().visibility,
+            CellVisibility::Flagged(2)
+        );
+        assert_eq!(game_state.history_len(), 2);
+        game_state.unflag(0, 0);
+        assert_eq!(game_state.remaining_mines(), 2);
+
+        // the 3 could be split as 2 and 1 either way round.
+        let deductions = crate::solver::Solver::deductions(&game_state);
+        assert!(deductions.mines.is_empty());
+
+        game_state.flag(0, 0);
+        game_state.flag(2, 0);
+        assert_eq!(game_state.game_condition, GameCondition::Won);
+        assert_eq!(game_state.to_string(), "3 1 3 mines-per-cell-2\n{FF}3F\n");
+    }
+
+    #[test]
+    fn test_game_errors() {
+        assert_eq!(
+            GameState::try_new(0, 5, 1).err(),
+            Some(GameError::InvalidDimensions {
+                width: 0,
+                height: 5
+            })
+        );
+        assert_eq!(
+            GameState::try_new(3, 3, 9).err(),
+            Some(GameError::TooManyMines {
+                mines: 9,
+                capacity: 9
+            })
+        );
+        assert!(GameState::try_with_rng_multi_mine(3, 3, 9, 2, &mut thread_rng()).is_ok());
+
+        let mut game_state: GameState = "3 2 1\n*##\n###".parse().unwrap();
+        assert_eq!(
+            game_state.try_click(3, 0),
+            Err(GameError::OutOfBounds { pos: (3, 0) })
+        );
+        assert_eq!(game_state.history_len(), 0);
+        game_state.try_flag(0, 0).unwrap();
+
+        assert_eq!(game_state.game_condition, GameCondition::Won);
+        assert_eq!(game_state.try_click(2, 1), Err(GameError::GameOver));
+
+        // clicking a flagged mine or flagging a revealed cell is refused.
+        let mut flagged: GameState = "3 2 2\n**#\n###".parse().unwrap();
+        flagged.try_flag(0, 0).unwrap();
+        assert_eq!(
+            flagged.try_click(0, 0),
+            Err(GameError::IllegalMove {
+                event: Event::Click { pos: (0, 0) },
+                reason: "the cell is flagged"
+            })
+        );
+        flagged.try_click(2, 1).unwrap();
+        assert!(matches!(
+            flagged.try_flag(2, 1),
+            Err(GameError::IllegalMove { .. })
+        ));
+        assert_eq!(flagged.game_condition, GameCondition::InProgress);
+
+        let other = GameState::try_new(2, 2, 1).unwrap();
+        assert_eq!(
+            game_state.try_validate(&other),
+            Err(GameError::MismatchedBoards {
+                expected: (3, 2),
+                found: (2, 2)
+            })
+        );
+        assert!(!game_state.validate(&other));
+    }
+
+    #[test]
+    fn test_move_outcome() {
+        let mut game_state: GameState = "4 3 1\n*###\n####\n####".parse().unwrap();
+        game_state.flood_fill = true;
+        let outcome = game_state.click(3, 2);
+        assert_eq!(outcome.revealed.len(), 11);
+        assert!(outcome.revealed.contains(&((1, 1), 1)));
+        assert!(outcome.revealed.contains(&((3, 0), 0)));
+        assert!(outcome.flagged.is_empty());
+        assert!(!outcome.hit_mine);
+        // that was every safe cell.
+        assert_eq!(outcome.condition.1, GameCondition::Won);
+
+        // clicking a revealed cell changes nothing.
+        assert!(game_state.click(3, 2).is_empty());
+
+        let mut game_state: GameState = "3 2 1\n*##\n###".parse().unwrap();
+        let outcome = game_state.flag(0, 0);
+        assert_eq!(outcome.flagged, vec![(0, 0)]);
+        assert_eq!(
+            outcome.condition,
+            (GameCondition::InProgress, GameCondition::Won)
+        );
+        assert!(outcome.ended_game());
+
+        let mut game_state: GameState = "2 1 1\n*#".parse().unwrap();
+        let outcome = game_state.click(0, 0);
+        assert!(outcome.hit_mine);
+        assert!(outcome.revealed.is_empty());
+        assert_eq!(outcome.condition.1, GameCondition::Lost);
+
+        let mut game_state: GameState = "3 2 1\n*##\n###".parse().unwrap();
+        game_state.click(1, 0);
+        let history_len = game_state.history_len();
+        assert!(game_state.flag(1, 0).is_empty());
+        assert_eq!(
+            game_state.at(1, 0).unwrap().visibility,
+            CellVisibility::Empty(1)
+        );
+        assert_eq!(game_state.history_len(), history_len);
+
+        // revealing every safe cell wins without flags, and undoing that move takes the win back.
+        let mut game_state: GameState = "3 1 1\n*##".parse().unwrap();
+        game_state.flood_fill = true;
+        let outcome = game_state.click(2, 0);
+        assert_eq!(outcome.revealed.len(), 2);
+        assert_eq!(
+            outcome.condition,
+            (GameCondition::InProgress, GameCondition::Won)
+        );
+        game_state.undo();
+        assert_eq!(game_state.game_condition, GameCondition::InProgress);
+        game_state.redo();
+        assert_eq!(game_state.game_condition, GameCondition::Won);
     }
 }
@@ -0,0 +1,261 @@
+// boards that can be solved from their first click without ever guessing, for fair competitions and
+// for benchmarking strategies that only deduce.
+//
+// a board is judged by playing it out with the deterministic strategies, including `SubsetDetection`
+// and `MineCountDetection`, and it passes when they win it.
+// when they get stuck, one of the mines next to the revealed area is moved somewhere that hasn't been
+// reached yet and the board is judged again. after a few such mutations the generator starts over
+// from a fresh board, until it runs out of attempts.
+
+use std::{
+    error::Error,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+
+use crate::game::*;
+use crate::solver::{MineCountDetection, Solver, SubsetDetection};
+
+// the strategies that decide whether a board can be solved without guessing.
+pub fn judge() -> Solver {
+    let mut solver = Solver::new();
+    solver.add_strategy(Box::new(SubsetDetection));
+    solver.add_strategy(Box::new(MineCountDetection));
+    solver
+}
+
+// clicks `first_click` and applies the judge's moves until it runs out of them.
+pub fn play_out(game_state: &GameState, first_click: (usize, usize)) -> GameState {
+    let mut game_state = game_state.snapshot();
+    game_state.click(first_click.0, first_click.1);
+    let mut solver = judge();
+    solver.init(&game_state);
+    while game_state.game_condition == GameCondition::InProgress {
+        let events = solver.next_clicks(&game_state);
+        if events.is_empty() {
+            break;
+        }
+        for event in events {
+            let outcome = game_state.apply(event);
+            if game_state.game_condition != GameCondition::InProgress {
+                break;
+            }
+            solver.update_from(&game_state, &outcome);
+        }
+    }
+    game_state
+}
+
+// whether the judge gets all the way through a board.
+pub fn solves_without_guessing(game_state: &GameState, first_click: (usize, usize)) -> bool {
+    play_out(game_state, first_click).game_condition == GameCondition::Won
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenerateError {
+    pub attempts: usize,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no board could be solved without guessing after {} attempts in {:.1?}",
+            self.attempts, self.elapsed
+        )
+    }
+}
+
+impl Error for GenerateError {}
+
+pub struct Generated {
+    // the board before its first click.
+    pub game_state: GameState,
+    pub first_click: (usize, usize),
+    // number of boards that were judged, including the one that was accepted.
+    pub attempts: usize,
+    pub elapsed: Duration,
+}
+
+pub struct NoGuessGenerator {
+    // the most boards that are judged before giving up.
+    pub max_attempts: usize,
+    // how many times a board is mutated before starting over from a fresh one.
+    pub mutations: usize,
+}
+
+impl Default for NoGuessGenerator {
+    fn default() -> Self {
+        NoGuessGenerator {
+            max_attempts: 1000,
+            mutations: 20,
+        }
+    }
+}
+
+impl NoGuessGenerator {
+    // `new_board` draws a fresh board, which decides its size, mine count and rules. the first click
+    // is the middle of the board, and its neighborhood is kept free of mines.
+    pub fn generate<F, R>(&self, mut new_board: F, rng: &mut R) -> Result<Generated, GenerateError>
+    where
+        F: FnMut(&mut R) -> GameState,
+        R: Rng + ?Sized,
+    {
+        let start = Instant::now();
+        let mut attempts = 0;
+        'boards: while attempts < self.max_attempts {
+            let mut game_state = new_board(rng);
+            let first_click = (game_state.width / 2, game_state.height / 2);
+            let mut opening = game_state.neighbors(first_click.0, first_click.1);
+            opening.push(first_click);
+            if !clear(&mut game_state, &opening, rng) {
+                attempts += 1;
+                continue;
+            }
+            for _ in 0..=self.mutations {
+                if attempts == self.max_attempts {
+                    break 'boards;
+                }
+                attempts += 1;
+                let played = play_out(&game_state, first_click);
+                if played.game_condition == GameCondition::Won {
+                    return Ok(Generated {
+                        game_state,
+                        first_click,
+                        attempts,
+                        elapsed: start.elapsed(),
+                    });
+                }
+                if !mutate(&mut game_state, &played, &opening, rng) {
+                    continue 'boards;
+                }
+            }
+        }
+        Err(GenerateError {
+            attempts,
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+// moves one mine from `from` to a random cell among `to` that has room for it.
+fn move_mine<R: Rng + ?Sized>(
+    game_state: &mut GameState,
+    from: usize,
+    to: &[usize],
+    rng: &mut R,
+) -> bool {
+    let mines_per_cell = game_state.mines_per_cell;
+    let to: Vec<usize> = to
+        .iter()
+        .copied()
+        .filter(|i| game_state.field[*i].state.mines() < mines_per_cell)
+        .collect();
+    if to.is_empty() {
+        return false;
+    }
+    let to = to[rng.gen_range(0..to.len())];
+    let state = |mines| match mines {
+        0 => CellState::Empty,
+        mines => CellState::Mine(mines),
+    };
+    game_state.field[from].state = state(game_state.field[from].state.mines() - 1);
+    game_state.field[to].state = state(game_state.field[to].state.mines() + 1);
+    true
+}
+
+// moves every mine out of `cells`, returning whether there was room for them elsewhere.
+fn clear<R: Rng + ?Sized>(
+    game_state: &mut GameState,
+    cells: &[(usize, usize)],
+    rng: &mut R,
+) -> bool {
+    let width = game_state.width;
+    let cells: Vec<usize> = cells.iter().map(|(x, y)| y * width + x).collect();
+    let elsewhere: Vec<usize> = (0..game_state.field.len())
+        .filter(|i| !cells.contains(i))
+        .collect();
+    for i in cells {
+        while game_state.field[i].state.mines() > 0 {
+            if !move_mine(game_state, i, &elsewhere, rng) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// the judge got stuck on `played`, so move one of the mines that border the revealed area to a cell
+// that it never reached. returns false when there is nothing left to move.
+fn mutate<R: Rng + ?Sized>(
+    game_state: &mut GameState,
+    played: &GameState,
+    opening: &[(usize, usize)],
+    rng: &mut R,
+) -> bool {
+    let width = played.width;
+    let revealed = |(x, y): (usize, usize)| {
+        matches!(
+            played.at(x, y).unwrap().visibility,
+            CellVisibility::Empty(_)
+        )
+    };
+    let (mut border, mut unreached) = (Vec::new(), Vec::new());
+    for i in 0..played.field.len() {
+        let pos = (i % width, i / width);
+        if revealed(pos) || opening.contains(&pos) {
+            continue;
+        }
+        if played.neighbors(pos.0, pos.1).into_iter().any(revealed) {
+            if game_state.field[i].state.mines() > 0 {
+                border.push(i);
+            }
+        } else if played.field[i].visibility == CellVisibility::Unknown {
+            unreached.push(i);
+        }
+    }
+    if border.is_empty() {
+        return false;
+    }
+    let from = border[rng.gen_range(0..border.len())];
+    move_mine(game_state, from, &unreached, rng)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_generated_boards_need_no_guesses() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let generator = NoGuessGenerator::default();
+        for _ in 0..3 {
+            let generated = generator
+                .generate(|rng| GameState::with_rng(9, 9, 10, rng), &mut rng)
+                .unwrap();
+            let (x, y) = generated.first_click;
+            assert_eq!(generated.game_state.bomb_count, 10);
+            assert_eq!(generated.game_state.neighboring_mines(x, y), 0);
+            assert!(solves_without_guessing(
+                &generated.game_state,
+                generated.first_click
+            ));
+            assert!(generated.attempts <= generator.max_attempts);
+        }
+
+        // a board too full to keep the first click clear can't be generated.
+        let generator = NoGuessGenerator {
+            max_attempts: 5,
+            mutations: 2,
+        };
+        let error = generator
+            .generate(|rng| GameState::with_rng(3, 3, 8, rng), &mut rng)
+            .err()
+            .unwrap();
+        assert_eq!(error.attempts, 5);
+    }
+}
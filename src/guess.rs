@@ -0,0 +1,478 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use rayon::prelude::*;
+
+use crate::game::*;
+use crate::solver::Solver;
+use crate::CombinationIterator;
+
+// approximation of ln(n!).
+pub fn ramanujan_approximation(n: f32) -> f32 {
+    if n < 1.0 {
+        // ln(0!) is 0, while the approximation below is NaN.
+        return 0.0;
+    }
+    n * n.ln() - n
+        + (n * (1.0 + 4.0 * n * (1.0 + 2.0 * n))).ln() / 6.0
+        + std::f32::consts::PI.ln() / 2.0
+}
+
+// result of the combinatoric search over the unknown cells of a board.
+pub struct Tally {
+    // for each unknown cell index, the number of consistent hypotheses with at least one mine in that cell.
+    pub histogram: BTreeMap<usize, usize>,
+    // for each unknown cell index, the number of consistent hypotheses that were enumerated for its group.
+    // cells in groups that were too large to enumerate have no entry.
+    pub samples: BTreeMap<usize, usize>,
+    pub unknown_count: usize,
+    pub group_sizes: Vec<usize>,
+    // whether partitions and combinations were searched, rather than giving up on the board.
+    pub searched: bool,
+}
+
+// note: every collection here is ordered (BTreeMap/BTreeSet) and ties are broken by cell index,
+// so the tally only depends on the board and never on hashing or rayon scheduling.
+pub fn tally(game_state: &GameState) -> Tally {
+    let mut unknown_cells = Vec::new();
+    let (width, _) = (game_state.width, game_state.height);
+    let mines_per_cell = game_state.mines_per_cell;
+    // how many more mines a cell could hold as far as the player can tell. a partly flagged cell on a
+    // board with several mines per cell is searched like an unknown one, but it can't be clicked.
+    let room = |cell: Cell| match cell.visibility {
+        CellVisibility::Unknown => mines_per_cell,
+        CellVisibility::Flagged(flags) => mines_per_cell.saturating_sub(flags),
+        CellVisibility::Empty(_) => 0,
+    };
+    for (x, y, cell) in game_state
+        .field
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (i % width, i / width, e))
+    {
+        if room(*cell) > 0 {
+            unknown_cells.push((x, y));
+        }
+    }
+    // execute optimal guessing strategy:
+    // partition unknown cells into territory based groups.
+    // iterate through all possible partitions of bomb counts for the given number of groups.
+    // then for each partition and presupposition of bomb counts per group, iterate through all combinations of bomb positions, checking for hint consistency between current board and hypothetical board.
+    // if the hypothetical is consistent with the current board, track probabilities
+    // after all this is over, select all the cells that had a 0 probability of having a bomb.
+
+    let mut groups: Vec<BTreeSet<(usize, usize)>> = Vec::new();
+    let mut ungrouped_cells: BTreeSet<(usize, usize)> = unknown_cells.iter().cloned().collect();
+    let mut check_queue = Vec::new();
+    // while there are any ungrouped cells, start a new group from the lowest ungrouped cell
+    while let Some(first_ungrouped_cell) = ungrouped_cells.pop_first() {
+        let mut group = BTreeSet::new();
+        group.insert(first_ungrouped_cell);
+        check_queue.push(first_ungrouped_cell);
+        // grow the currently active group by consuming the check queue
+        while let Some(cell) = check_queue.pop() {
+            let neighborhood = game_state
+                .neighbors(cell.0, cell.1)
+                .iter()
+                .flat_map(|e| game_state.neighbors(e.0, e.1))
+                .collect::<BTreeSet<_>>();
+            for neighbor in neighborhood.iter() {
+                if *neighbor == cell {
+                    continue;
+                }
+                let neighbor_cell = game_state.at(neighbor.0, neighbor.1).unwrap();
+                if ungrouped_cells.contains(neighbor) && room(neighbor_cell) > 0 {
+                    group.insert(*neighbor);
+                    check_queue.push(*neighbor);
+                    ungrouped_cells.remove(neighbor);
+                }
+            }
+        }
+        // check queue must be empty now, so continue to the next ungrouped cell and start a new group.
+        groups.push(group);
+    }
+    // only what the player can see, rather than how many flags are correct.
+    let remaining_mines = game_state.mines_left();
+
+    let mut histogram = BTreeMap::new();
+    for (x, y) in unknown_cells.iter() {
+        if game_state.at(*x, *y).unwrap().visibility == CellVisibility::Unknown {
+            histogram.insert(x + y * width, 0usize);
+        }
+    }
+    let mut samples = BTreeMap::new();
+
+    let empty_iter: Vec<Vec<usize>> = vec![vec![]];
+    let iter: Box<dyn Iterator<Item = Vec<usize>>> = if groups.len() <= 1 {
+        Box::new(empty_iter.iter().cloned())
+    } else {
+        Box::new(CombinationIterator::new(remaining_mines, groups.len() - 1))
+    };
+
+    let search_scale = (ramanujan_approximation(remaining_mines as f32)
+        - ramanujan_approximation(groups.len() as f32 - 1.0)
+        - ramanujan_approximation(1.0 + remaining_mines as f32 - groups.len() as f32))
+        / 10.0f32.ln();
+    // the partitions need a cut between every pair of groups, so with fewer mines than that there is
+    // nothing to enumerate.
+    let searched = remaining_mines + 1 >= groups.len() && search_scale < 3.0;
+    if searched {
+        let mut collected = iter.collect::<Vec<_>>();
+        // each local tally maps a cell index to (mine hits, consistent hypotheses).
+        let collected_histograms: BTreeMap<usize, (usize, usize)> = collected
+            .par_iter_mut()
+            .map(|partition| {
+                partition.insert(0, 0);
+                partition.push(remaining_mines);
+
+                let mine_counts: Vec<_> = partition.windows(2).map(|w| w[1] - w[0]).collect();
+                if groups.iter().enumerate().any(|(i, e)| {
+                    mine_counts[i]
+                        > e.iter()
+                            .map(|(x, y)| room(game_state.at(*x, *y).unwrap()))
+                            .sum::<usize>()
+                }) {
+                    return BTreeMap::new();
+                }
+                let mut local_histogram = BTreeMap::new();
+                for (group_idx, group) in groups.iter().enumerate() {
+                    let remaining_mines = mine_counts[group_idx];
+                    let remaining_mines_float = remaining_mines as f32;
+                    let unknown_cells: Vec<_> = group.iter().cloned().collect();
+                    // each cell has a slot for every mine it can still hold, and the slots of a cell
+                    // are next to each other.
+                    let mut slot_cells = Vec::new();
+                    for (i, (x, y)) in unknown_cells.iter().enumerate() {
+                        let cell = game_state.at(*x, *y).unwrap();
+                        slot_cells.extend(std::iter::repeat_n(i, room(cell)));
+                    }
+                    let slots = slot_cells.len();
+                    let unknown_cells_float = slots as f32;
+                    let sub = unknown_cells_float - remaining_mines_float;
+
+                    // calculate order of magnitude of combinations that need to be searched.
+                    let search_scale = (ramanujan_approximation(unknown_cells_float)
+                        - ramanujan_approximation(remaining_mines_float)
+                        - ramanujan_approximation(sub))
+                        / 10.0f32.ln();
+
+                    if search_scale < 3.0 {
+                        // need to generate combinations and track valid solutions.
+                        let mut hypothetical = game_state.snapshot();
+
+                        // with no mines in the group, the only combination is the empty one.
+                        let combinations: Box<dyn Iterator<Item = Vec<usize>>> =
+                            if remaining_mines == 0 {
+                                Box::new(std::iter::once(Vec::new()))
+                            } else {
+                                Box::new(CombinationIterator::new(slots, remaining_mines))
+                            };
+                        for combination in combinations {
+                            // combination is the indices into the slots of local unknown_cells. the
+                            // slots of a cell fill up in order, so that each way of spreading the mines
+                            // over the cells is only visited once.
+                            if combination.iter().enumerate().any(|(i, slot)| {
+                                *slot > 0
+                                    && slot_cells[slot - 1] == slot_cells[*slot]
+                                    && (i == 0 || combination[i - 1] != slot - 1)
+                            }) {
+                                continue;
+                            }
+                            let mut counts = vec![0; unknown_cells.len()];
+                            for slot in combination.iter() {
+                                counts[slot_cells[*slot]] += 1;
+                            }
+                            for ((x, y), count) in unknown_cells.iter().zip(counts.iter()) {
+                                let cell = hypothetical.at_mut(*x, *y).unwrap();
+                                // the flags of a partly flagged cell cover mines of their own.
+                                cell.state = match cell.visibility.flags() + count {
+                                    0 => CellState::Empty,
+                                    mines => CellState::Mine(mines),
+                                };
+                            }
+                            if game_state.validate(&hypothetical) {
+                                // if game_state and hypothetical were compatible, it means that either state could have resulted in the current visible appearance.
+                                // for each bomb position in the hypothetical, add 1 to its position in the histogram
+                                for cell in unknown_cells.iter() {
+                                    local_histogram
+                                        .entry(cell.1 * width + cell.0)
+                                        .or_insert((0usize, 0usize))
+                                        .1 += 1;
+                                }
+                                for (cell, count) in unknown_cells.iter().zip(counts.iter()) {
+                                    if *count > 0 {
+                                        local_histogram
+                                            .entry(cell.1 * width + cell.0)
+                                            .or_insert((0, 0))
+                                            .0 += 1;
+                                    }
+                                }
+                            }
+                        }
+                    } else {
+                        // too many combinations, so every cell in the group is counted once without being sampled.
+                        for cell in unknown_cells.iter() {
+                            local_histogram
+                                .entry(cell.1 * width + cell.0)
+                                .or_insert((0usize, 0usize))
+                                .0 += 1;
+                        }
+                    }
+                }
+                local_histogram
+            })
+            .reduce(BTreeMap::new, |mut a, b| {
+                b.iter().for_each(|e| {
+                    let entry = a.entry(*e.0).or_insert((0, 0));
+                    entry.0 += (e.1).0;
+                    entry.1 += (e.1).1;
+                });
+                a
+            });
+        // fold parallel histograms into main histogram
+        // partly flagged cells are left out, since they can't be clicked.
+        for (idx, (hits, count)) in collected_histograms {
+            if let Some(entry) = histogram.get_mut(&idx) {
+                *entry += hits;
+                if count > 0 {
+                    samples.insert(idx, count);
+                }
+            }
+        }
+    } else {
+        histogram.values_mut().for_each(|v| *v += 1);
+    }
+
+    Tally {
+        histogram,
+        samples,
+        unknown_count: unknown_cells.len(),
+        group_sizes: groups.iter().map(|e| e.len()).collect(),
+        searched,
+    }
+}
+
+impl Tally {
+    // estimated chance of each unknown cell (by index) containing a mine.
+    // cells that could not be sampled fall back to the density of the remaining mines.
+    pub fn probabilities(&self, remaining_mines: usize) -> BTreeMap<usize, f32> {
+        let density = remaining_mines as f32 / self.unknown_count.max(1) as f32;
+        self.histogram
+            .iter()
+            .map(|(idx, hits)| match self.samples.get(idx) {
+                Some(count) => (*idx, (*hits as f32 / *count as f32).min(1.0)),
+                None => (*idx, density.min(1.0)),
+            })
+            .collect()
+    }
+}
+
+pub fn mine_probabilities(game_state: &GameState) -> BTreeMap<usize, f32> {
+    tally(game_state).probabilities(game_state.mines_left())
+}
+
+pub fn educated_guess(
+    game_state: &mut GameState,
+    guess_count: &mut usize,
+    saved_valid_clicks: &mut Vec<Event>,
+) -> Event {
+    let tally = tally(game_state);
+    guess_from_tally(game_state, &tally, guess_count, saved_valid_clicks)
+}
+
+// the guessing half of `educated_guess`, for callers that also want to look at the tally.
+pub fn guess_from_tally(
+    game_state: &GameState,
+    tally: &Tally,
+    guess_count: &mut usize,
+    saved_valid_clicks: &mut Vec<Event>,
+) -> Event {
+    let width = game_state.width;
+    let histogram = &tally.histogram;
+    if histogram.is_empty() {
+        // on boards that hold several mines per cell, every cell can be revealed or flagged while the
+        // last mines are still missing flags. all that's left is to guess which cell has another one.
+        // once the flags add up to the mines there's nothing left to guess, even if some are wrong.
+        if game_state.mines_left() == 0 {
+            return Event::None;
+        }
+        *guess_count += 1;
+        return game_state
+            .field
+            .iter()
+            .position(|c| {
+                matches!(c.visibility, CellVisibility::Flagged(flags) if flags < game_state.mines_per_cell)
+            })
+            .map_or(Event::None, |i| Event::Flag {
+                pos: (i % width, i / width),
+            });
+    }
+
+    // now that the histogram has been tallied, select one of the cells with the lowest probability of being a bomb.
+    // the histogram is ordered by cell index and the sort is stable, so ties always resolve to the lowest index.
+    let mut augmented_histogram: Vec<(usize, usize)> =
+        histogram.iter().map(|(k, v)| (*k, *v)).collect();
+    augmented_histogram.sort_by_key(|e| e.1);
+
+    let index = augmented_histogram[0].0;
+    // if we have some nonzero number of cells that have been combinatorically deduced to not be mines,
+    if augmented_histogram[0].1 == 0 {
+        // add all but the 1st to a list so that they can be clicked on later without wasting additional computational effort.
+        for (idx, ct) in augmented_histogram.iter().skip(1) {
+            if *ct == 0 {
+                saved_valid_clicks.push(Event::Click {
+                    pos: (*idx % width, *idx / width),
+                });
+            } else {
+                break;
+            }
+        }
+    }
+
+    if augmented_histogram[0].1 > 0 {
+        // since the chance of the picked entry being a bomb is nonzero, add 1 to the guess count to indicate that actual guesses (rather than combinatoric deductions) are being performed
+        *guess_count += 1;
+    }
+    let (x, y) = (index % width, index / width);
+    Event::Click { pos: (x, y) }
+}
+
+// what the search behind a guess found, for logging. guessing itself never prints, so that it can run
+// behind a server or a terminal ui.
+pub fn describe_tally(game_state: &GameState, tally: &Tally) -> String {
+    let mut description = format!(
+        "partitioned {} bombs into {} unknown_cells: {} groups total, {:?} distribution",
+        game_state.remaining_mines(),
+        tally.unknown_count,
+        tally.group_sizes.len(),
+        tally.group_sizes
+    );
+    if tally.searched {
+        description.push_str("\nsearched partitions and combinations.");
+    }
+    if tally.histogram.is_empty() {
+        return description;
+    }
+    let mut augmented_histogram: Vec<(usize, usize)> =
+        tally.histogram.iter().map(|(k, v)| (*k, *v)).collect();
+    augmented_histogram.sort_by_key(|e| e.1);
+    if augmented_histogram.len() < 100 {
+        description.push_str(&format!(
+            "\nguessed combinatorically, unknown: {}, remaining mines: {}. pdf was {:?}",
+            tally.unknown_count,
+            game_state.remaining_mines(),
+            augmented_histogram,
+        ));
+    } else {
+        description.push_str("\nguessing randomly");
+    }
+    description
+}
+
+// the solver's suggestion for the current position, without applying it.
+pub fn hint(game_state: &GameState) -> Event {
+    let mut solver = Solver::new();
+    solver.init(game_state);
+    match solver.next_clicks(game_state).first() {
+        Some(event) => *event,
+        None if game_state.game_condition == GameCondition::InProgress => {
+            educated_guess(&mut game_state.snapshot(), &mut 0, &mut Vec::new())
+        }
+        None => Event::None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn play_seeded_game(seed: u64, threads: usize) -> Vec<Event> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut game_state = GameState::with_rng(9, 9, 10, &mut rng);
+            let mut solver = Solver::new();
+            solver.init(&game_state);
+            let mut saved_valid_clicks = Vec::new();
+            let mut guess_count = 0;
+            let mut log = Vec::new();
+            while game_state.game_condition == GameCondition::InProgress {
+                let mut events = solver.next_clicks(&game_state);
+                events.append(&mut saved_valid_clicks);
+                if events.is_empty() {
+                    events.push(educated_guess(
+                        &mut game_state,
+                        &mut guess_count,
+                        &mut saved_valid_clicks,
+                    ));
+                }
+                for event in events {
+                    let outcome = game_state.apply(event);
+                    log.push(event);
+                    if game_state.game_condition != GameCondition::InProgress {
+                        break;
+                    }
+                    solver.update_from(&game_state, &outcome);
+                }
+            }
+            log
+        })
+    }
+
+    #[test]
+    fn test_mine_probabilities() {
+        // the mine is next to both 1s, either at (1, 0) or (1, 1).
+        let game_state: GameState = "3 2 1\n1*#\n1##".parse().unwrap();
+        let probabilities = mine_probabilities(&game_state);
+        assert_eq!(probabilities.get(&1), Some(&0.5));
+        assert_eq!(probabilities.get(&4), Some(&0.5));
+        assert_eq!(probabilities.get(&2), Some(&0.0));
+        assert_eq!(probabilities.get(&5), Some(&0.0));
+        assert_eq!(probabilities.get(&0), None);
+    }
+
+    #[test]
+    fn test_multi_mine_probabilities() {
+        // both 2s see the same two cells, which can hold their mines as 2 and 0, 1 and 1 or 0 and 2.
+        let game_state: GameState = "3 2 2 mines-per-cell-2\n2{**}#\n2##".parse().unwrap();
+        let probabilities = mine_probabilities(&game_state);
+        assert_eq!(probabilities.get(&1), Some(&(2.0 / 3.0)));
+        assert_eq!(probabilities.get(&4), Some(&(2.0 / 3.0)));
+        assert_eq!(probabilities.get(&2), Some(&0.0));
+        assert_eq!(probabilities.get(&5), Some(&0.0));
+    }
+
+    #[test]
+    fn test_hidden_mines_under_flags() {
+        // the flagged cell holds a second mine on one board and not on the other, which can't be seen.
+        let hidden: GameState = "3 2 2 mines-per-cell-2\n2{F*}#\n2##".parse().unwrap();
+        let elsewhere: GameState = "3 2 2 mines-per-cell-2\n2F#\n2*#".parse().unwrap();
+        let probabilities = mine_probabilities(&hidden);
+        assert_eq!(probabilities, mine_probabilities(&elsewhere));
+        assert_eq!(probabilities.get(&4), Some(&0.5));
+        assert_eq!(probabilities.get(&2), Some(&0.0));
+        // the flagged cell can't be clicked, so it has no probability.
+        assert_eq!(probabilities.get(&1), None);
+    }
+
+    #[test]
+    fn test_more_groups_than_mines() {
+        // one mine left over four separate groups can't be partitioned, so the search is skipped.
+        let game_state: GameState = "20 1 1\n*1...#....#....#....".parse().unwrap();
+        let tally = tally(&game_state);
+        assert!(!tally.searched);
+        assert_eq!(tally.group_sizes.len(), 4);
+    }
+
+    #[test]
+    fn test_event_sequence_independent_of_thread_count() {
+        for seed in 0..4 {
+            let single = play_seeded_game(seed, 1);
+            assert_eq!(single, play_seeded_game(seed, 4));
+            assert_eq!(single, play_seeded_game(seed, 1));
+        }
+    }
+}
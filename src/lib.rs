@@ -1,3 +1,17 @@
+pub mod encoding;
+pub mod frames;
+pub mod game;
+pub mod generator;
+pub mod guess;
+pub mod protocol;
+pub mod render;
+pub mod replay;
+pub mod server;
+pub mod snapshot;
+pub mod solver;
+pub mod text_format;
+pub mod tui;
+
 pub struct CombinationIterator {
     state: Vec<usize>,
     n: usize,
@@ -28,7 +42,6 @@ impl Iterator for CombinationIterator {
         if *last < self.n - 1 {
             *last += 1;
         } else {
-            drop(last);
             let mut last_idx = self.r - 1;
             loop {
                 // println!("last_idx = {}", last_idx);
@@ -58,6 +71,7 @@ impl Iterator for CombinationIterator {
 }
 
 #[cfg(test)]
+#[allow(unused_mut, clippy::identity_op, clippy::useless_vec)]
 mod test {
     use super::*;
     #[test]
@@ -1,30 +1,24 @@
 use std::{
-    borrow::Borrow,
-    collections::{HashMap, HashSet},
+    collections::BTreeMap,
+    io::BufRead,
+    path::{Path, PathBuf},
 };
 
 use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Scale, Window, WindowOptions};
 pub use rand::prelude::*;
-use rayon::prelude::*;
 use structopt::StructOpt;
 
-mod game;
-mod lib;
-mod solver;
-
-use game::*;
-use lib::CombinationIterator;
-use solver::*;
-
-pub fn rgb_to_u32(r: u8, g: u8, b: u8) -> u32 {
-    ((r as u32) << 16) | ((g as u32) << 8) | (b as u32)
-}
-
-pub fn ramanujan_approximation(n: f32) -> f32 {
-    n * n.ln() - n
-        + (n * (1.0 + 4.0 * n * (1.0 + 2.0 * n))).ln() / 6.0
-        + std::f32::consts::PI.ln() / 2.0
-}
+use minesweeper_rs::frames::*;
+use minesweeper_rs::game::*;
+use minesweeper_rs::generator::{judge, NoGuessGenerator};
+use minesweeper_rs::guess::{describe_tally, guess_from_tally, hint, tally};
+use minesweeper_rs::protocol::BotSession;
+use minesweeper_rs::render::*;
+use minesweeper_rs::replay::*;
+use minesweeper_rs::server::{GameOptions, GameServer};
+use minesweeper_rs::snapshot::*;
+use minesweeper_rs::solver::*;
+use minesweeper_rs::tui::{self, KeyCode, Terminal};
 
 #[derive(Debug, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
@@ -47,67 +41,297 @@ struct Opt {
     #[structopt(long, default_value = "1500")]
     pub num_bombs: usize,
 
+    // `beginner` (9x9 with 10 mines), `intermediate` (16x16 with 40), `expert` (30x16 with 99) or
+    // `custom`, which takes the size from `-w` and `-h` and the mines from `--num-bombs` or `--density`.
+    #[structopt(long, default_value = "custom")]
+    pub difficulty: Difficulty,
+
+    // fraction of the cells that are mines, instead of `--num-bombs`. on 3d boards it counts the cells
+    // of every layer, and with several mines per cell it can go past 1.
+    #[structopt(long)]
+    pub density: Option<f32>,
+
     #[structopt(long, default_value = "0")]
     pub delay_ms: usize,
+
+    // `bounded`, or `wrap-x`, `wrap-y` or `torus` for boards whose edges wrap around to the other side.
+    #[structopt(long, default_value = "bounded")]
+    pub topology: Topology,
+
+    // `moore` for the 8 cells around a square, or `hex` for a hex grid with 6 neighbors per cell.
+    // hex cells are drawn as hexagons, so `--tile` has to be 4 or more. other kernels on a square grid are
+    // `von-neumann`, `knight`, `radius-<n>` or a custom symmetric `kernel:<dx>,<dy>;<dx>,<dy>;...`.
+    #[structopt(long, default_value = "moore")]
+    pub neighborhood: Neighborhood,
+
+    // number of `width`x`height` layers, for 3d boards. cells also neighbor the layers above and below
+    // them, and the window shows one layer at a time: page up and page down move between them.
+    #[structopt(long, default_value = "1")]
+    pub depth: usize,
+
+    // the most mines a single cell can hold. numbers count every mine around a cell, and a cell needs
+    // a flag for each of its mines.
+    #[structopt(long, default_value = "1")]
+    pub mines_per_cell: usize,
+
+    // only play boards that can be solved from their opening without guessing. games start with the
+    // opening revealed, and the solver also uses the stronger strategies that judged the board.
+    #[structopt(long)]
+    pub no_guess: bool,
+
+    // the most boards that are tried for each game with `--no-guess` before falling back to a board
+    // that may need guesses.
+    #[structopt(long, default_value = "1000")]
+    pub no_guess_attempts: usize,
+
+    // size in pixels of each cell in the window. 1 draws a pixel per cell and scales the window up,
+    // larger tiles show digits, flags and grid lines. hex boards default to 8.
+    #[structopt(long, default_value = "1")]
+    pub tile: usize,
+
+    // `plain`, or `heat-map` to shade unknown cells by the mine probabilities from the last guess.
+    // cells that the guess proved safe and queued for clicking are highlighted in blue.
+    #[structopt(long, default_value = "plain")]
+    pub render_mode: RenderMode,
+
+    // seeds board generation. together with the deterministic solver and guesser, a seeded run
+    // produces the same sequence of games and moves regardless of `--threads`.
+    #[structopt(long)]
+    pub seed: Option<u64>,
+
+    // directory that the final position of every lost game is written to, in the text board format.
+    #[structopt(long, parse(from_os_str))]
+    pub capture_dir: Option<PathBuf>,
+
+    // also write an image of every lost game to `capture_dir`, as `png` or `ppm`, drawn with `--tile`.
+    #[structopt(long)]
+    pub capture_image: Option<ImageFormat>,
+
+    // draw the solver overlay for the position before the losing move onto captured images.
+    #[structopt(long)]
+    pub capture_overlay: bool,
+
+    // directory that the solver run is recorded to, with a frame every `--skip` applied events.
+    // works with `--silence` too.
+    #[structopt(long, parse(from_os_str))]
+    pub frame_dir: Option<PathBuf>,
+
+    // `gif` for one animation per game, or `png` or `ppm` for numbered frames.
+    #[structopt(long, default_value = "gif")]
+    pub frame_format: FrameFormat,
+
+    // directory that a replay of every finished game is written to.
+    #[structopt(long, parse(from_os_str))]
+    pub record_dir: Option<PathBuf>,
+
+    // play back a recorded replay instead of running the solver. use the left and right arrow keys
+    // to step through it and `g` to go back to before the last guess, or with `--silence`,
+    // enter `n`, `p`, `g` or `q` on stdin.
+    #[structopt(long, parse(from_os_str))]
+    pub replay: Option<PathBuf>,
+
+    // play the game yourself. left click reveals, right click flags, middle click chords. cells that
+    // can hold several mines take another flag with each right click until they are full, and shift
+    // right click takes one away.
+    // `n` starts a new game, `u` undoes, `r` redoes, `h` asks the solver for a hint
+    // and `s` toggles an overlay of what the solver knows about the board.
+    #[structopt(long)]
+    pub play: bool,
+
+    // use the terminal instead of a window, to watch the solver or, with `--play`, to play with the
+    // keyboard: arrow keys or `hjkl` move, space reveals, `f` flags, `F` takes a flag away, `c` chords,
+    // `u` undoes, `r` redoes, `?` asks for a hint, `n` starts a new game and `q` quits.
+    #[structopt(long)]
+    pub tui: bool,
+
+    // host games for an external bot, speaking newline delimited json on stdin and stdout.
+    // see `protocol.rs` for the messages.
+    #[structopt(long)]
+    pub bot: bool,
+
+    // host games over http and websockets at this address, `127.0.0.1:8080` if none is given. only
+    // loopback addresses are allowed. new games take their shape from the other board options.
+    // see `server.rs` for the endpoints.
+    #[structopt(long)]
+    pub serve: Option<Option<String>>,
+}
+
+// writes the final positions of lost games to disk, so that they can be loaded again as test cases,
+// and optionally an image of each one for reviewing them at a glance.
+struct LossCapture {
+    dir: PathBuf,
+    count: usize,
+    image: Option<ImageFormat>,
+    renderer: TileRenderer,
+    overlay: bool,
+}
+
+impl LossCapture {
+    fn new(dir: PathBuf, opt: &Opt) -> Self {
+        std::fs::create_dir_all(&dir).unwrap();
+        LossCapture {
+            dir,
+            count: 0,
+            image: opt.capture_image,
+            renderer: TileRenderer::new(opt.tile),
+            overlay: opt.capture_overlay,
+        }
+    }
+
+    fn capture(&mut self, game_state: &GameState) {
+        let path = self.dir.join(format!("loss_{:05}.txt", self.count));
+        if let Err(e) = std::fs::write(&path, game_state.to_string()) {
+            println!("failed to capture lost game to {}: {}", path.display(), e);
+        }
+        if let Some(format) = self.image {
+            // the overlay is only interesting for the position the losing move was made from.
+            let overlay = if self.overlay {
+                let mut before = game_state.clone();
+                before.undo();
+                Some(Overlay::new(&before))
+            } else {
+                None
+            };
+            let path = path.with_extension(format.extension());
+            let snapshot = Snapshot::new(game_state, &self.renderer, overlay.as_ref());
+            if let Err(e) = snapshot.save(&path) {
+                println!("failed to capture lost game to {}: {}", path.display(), e);
+            }
+        }
+        self.count += 1;
+    }
+}
+
+// records every game and writes its replay to disk once the game is over.
+struct ReplayCapture {
+    dir: PathBuf,
+    count: usize,
+    recorder: ReplayRecorder,
+}
+
+impl ReplayCapture {
+    fn new(dir: PathBuf, game_state: &GameState, seed: u64) -> Self {
+        std::fs::create_dir_all(&dir).unwrap();
+        ReplayCapture {
+            dir,
+            count: 0,
+            recorder: ReplayRecorder::new(game_state, Some(seed)),
+        }
+    }
+
+    fn record(&mut self, event: Event, source: &str) {
+        self.recorder.record(event, source);
+    }
+
+    // save the replay of the finished game, and start recording `game_state`.
+    fn restart(&mut self, game_state: &GameState, seed: u64) {
+        let recorder = std::mem::replace(
+            &mut self.recorder,
+            ReplayRecorder::new(game_state, Some(seed)),
+        );
+        let path = self.dir.join(format!("replay_{:05}.json", self.count));
+        self.count += 1;
+        if let Err(e) = recorder.finish().save(&path) {
+            println!("failed to save replay to {}: {}", path.display(), e);
+        }
+    }
+}
+
+// whether the terminal ui is drawing. it takes over the whole screen, so anything printed would end up
+// in the middle of the board.
+fn terminal_ui(opt: &Opt) -> bool {
+    opt.tui && (opt.play || !opt.silence)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn check_and_restart_game(
     game_state: &mut GameState,
     solver: &mut Solver,
     saved_valid_clicks: &mut Vec<Event>,
     guess_count: &mut usize,
     wins: &mut (usize, usize),
-    num_bombs: usize,
+    rng: &mut StdRng,
+    capture: &mut Option<LossCapture>,
+    recording: &mut Option<ReplayCapture>,
     opt: &Opt,
+    stuck: bool,
 ) -> bool {
+    // the full-field counts below are only needed once a game is over, or can't go on.
+    if game_state.game_condition == GameCondition::InProgress && !stuck {
+        return false;
+    }
     let mut restart = false;
+    if game_state.game_condition == GameCondition::InProgress {
+        if !terminal_ui(opt) {
+            println!(
+                "game abandoned, with {} remaining mines and no moves left\n\n\n",
+                game_state.remaining_mines()
+            );
+        }
+        restart = true;
+    }
     if game_state.game_condition == GameCondition::Lost {
-        println!(
-            "game lost, with {} remaining mines, {} unknown squares, and {} total guesses\n\n\n",
-            game_state.remaining_mines(),
-            game_state
-                .field
-                .iter()
-                .filter(|c| c.visibility == CellVisibility::Unknown)
-                .count(),
-            guess_count
-        );
+        if !terminal_ui(opt) {
+            println!(
+                "game lost, with {} remaining mines, {} unknown squares, and {} total guesses\n\n\n",
+                game_state.remaining_mines(),
+                game_state
+                    .field
+                    .iter()
+                    .filter(|c| c.visibility == CellVisibility::Unknown)
+                    .count(),
+                guess_count
+            );
+        }
+        if let Some(capture) = capture {
+            capture.capture(game_state);
+        }
         restart = true;
 
         // one_off = true;
     }
     if game_state.game_condition == GameCondition::Won {
-        for i in game_state
-            .field
-            .iter()
-            .enumerate()
-            .filter_map(|(i, e)| match e.visibility {
-                CellVisibility::Unknown => Some(i),
-                _ => None,
-            })
-            .collect::<Vec<usize>>()
-        {
+        // a game won by flagging every mine still has safe cells to show, while a game won by
+        // revealing them only has mines left.
+        let unknown = if game_state.all_safe_revealed() {
+            Vec::new()
+        } else {
+            game_state
+                .field
+                .iter()
+                .enumerate()
+                .filter_map(|(i, e)| match e.visibility {
+                    CellVisibility::Unknown => Some(i),
+                    _ => None,
+                })
+                .collect::<Vec<usize>>()
+        };
+        for i in unknown {
             let (x, y) = (i % game_state.width, i / game_state.width);
-            game_state.click(x, y);
-            if game_state.game_condition == GameCondition::Lost {
-                println!("{}, {} caused the game to lose after it had already been detected as won. contents were {:?}", x, y, game_state.at(x, y).unwrap());
+            if game_state.click(x, y).hit_mine {
+                if !terminal_ui(opt) {
+                    println!("{}, {} caused the game to lose after it had already been detected as won. contents were {:?}", x, y, game_state.at(x, y).unwrap());
+                }
                 break;
             }
         }
-        println!(
-            "game won, with {} remaining mines, {} unknown squares, and {} total guesses\n\n\n",
-            game_state.remaining_mines(),
-            game_state
-                .field
-                .iter()
-                .filter(|c| c.visibility == CellVisibility::Unknown)
-                .count(),
-            guess_count
-        );
+        if !terminal_ui(opt) {
+            println!(
+                "game won, with {} remaining mines, {} unknown squares, and {} total guesses\n\n\n",
+                game_state.remaining_mines(),
+                game_state
+                    .field
+                    .iter()
+                    .filter(|c| c.visibility == CellVisibility::Unknown)
+                    .count(),
+                guess_count
+            );
+        }
         restart = true;
         // one_off = true;
     }
     if restart {
+        let num_bombs = opt.num_bombs;
         if game_state.remaining_mines() as f32 / (num_bombs as f32) < 0.03
             && game_state.game_condition == GameCondition::Lost
             && !opt.silence
@@ -128,12 +352,17 @@ fn check_and_restart_game(
             // only count attempts that have more than 10 clicked/flagged cells. removes games that end really quickly from consideration
             wins.1 += 1;
         }
-        if wins.1 > 0 {
+        if wins.1 > 0 && !terminal_ui(opt) {
             println!("winrate: {}", wins.0 as f32 / wins.1 as f32);
         }
 
-        *game_state = GameState::new(game_state.width, game_state.height, num_bombs);
-        *solver = Solver::new();
+        // every game gets its own seed, so that it can be regenerated from its replay.
+        let seed = rng.gen();
+        *game_state = new_board(opt, seed);
+        if let Some(recording) = recording {
+            recording.restart(game_state, seed);
+        }
+        solver.reset(game_state);
         *guess_count = 0;
         saved_valid_clicks.clear();
         true
@@ -142,342 +371,693 @@ fn check_and_restart_game(
     }
 }
 
-fn educated_guess(
-    game_state: &mut GameState,
-    guess_count: &mut usize,
-    saved_valid_clicks: &mut Vec<Event>,
-) -> Event {
-    let mut event = Event::None;
-    let mut unknown_cells = Vec::new();
-    let (width, _) = (game_state.width, game_state.height);
-    for (x, y, cell) in game_state
-        .field
-        .iter()
-        .enumerate()
-        .map(|(i, e)| (i % width, i / width, e))
-    {
-        match cell {
-            Cell {
-                visibility: CellVisibility::Unknown,
-                ..
-            } => {
-                unknown_cells.push((x, y));
+// a board with the shape given on the command line.
+fn random_board(opt: &Opt, rng: &mut StdRng) -> GameState {
+    let mut game_state = GameState::with_rng_multi_mine(
+        opt.width,
+        opt.height * opt.depth,
+        opt.num_bombs,
+        opt.mines_per_cell,
+        rng,
+    );
+    game_state.topology = opt.topology;
+    game_state.neighborhood = opt.neighborhood.clone();
+    game_state.depth = opt.depth;
+    game_state
+}
+
+fn new_board(opt: &Opt, seed: u64) -> GameState {
+    let mut rng = StdRng::seed_from_u64(seed);
+    if !opt.no_guess {
+        return random_board(opt, &mut rng);
+    }
+    let generator = NoGuessGenerator {
+        max_attempts: opt.no_guess_attempts,
+        ..NoGuessGenerator::default()
+    };
+    match generator.generate(|rng| random_board(opt, rng), &mut rng) {
+        // stdout carries the line protocol in bot mode, so these go to stderr.
+        Ok(generated) => {
+            if !terminal_ui(opt) {
+                eprintln!(
+                    "generated a board without guesses in {:.1?} after {} attempts",
+                    generated.elapsed, generated.attempts
+                );
             }
-            _ => {}
+            // the opening is revealed like a first click by hand, without leaving it in the history.
+            let mut game_state = generated.game_state;
+            let (x, y) = generated.first_click;
+            game_state.flood_fill = true;
+            game_state.click(x, y);
+            game_state.flood_fill = false;
+            game_state.snapshot()
+        }
+        Err(e) => {
+            if !terminal_ui(opt) {
+                eprintln!("{}, playing a board that may need guesses", e);
+            }
+            random_board(opt, &mut StdRng::seed_from_u64(seed))
         }
     }
-    // execute optimal guessing strategy:
-    // partition unknown cells into territory based groups.
-    // iterate through all possible partitions of bomb counts for the given number of groups.
-    // then for each partition and presupposition of bomb counts per group, iterate through all combinations of bomb positions, checking for hint consistency between current board and hypothetical board.
-    // if the hypothetical is consistent with the current board, track probabilities
-    // after all this is over, select all the cells that had a 0 probability of having a bomb.
-
-    let mut groups: Vec<HashSet<(usize, usize)>> = Vec::new();
-    groups.push(HashSet::new());
-    let mut ungrouped_cells: HashSet<(usize, usize)> = unknown_cells.iter().cloned().collect();
-    let mut check_queue = Vec::new();
-    let mut group_idx = 0;
-    // while there are any ungrouped cells
-    while ungrouped_cells.len() > 0 {
-        let first_ungrouped_cell = ungrouped_cells.iter().take(1).next().unwrap();
-        groups[group_idx].insert(*first_ungrouped_cell);
-        check_queue.push(*first_ungrouped_cell);
-        // grow the currently active group by consuming the check queue
-        while check_queue.len() > 0 {
-            let cell = check_queue.pop().unwrap();
-            let neighborhood = game_state
-                .neighbors(cell.0, cell.1)
-                .iter()
-                .map(|e| game_state.neighbors(e.0, e.1))
-                .flatten()
-                .collect::<HashSet<_>>();
-            for neighbor in neighborhood.iter() {
-                if *neighbor == cell {
-                    continue;
+}
+
+// opens a window that fits the tiles of one layer of `game_state`. single pixel cells are scaled up
+// by the window, while tiles are shown as they are.
+fn open_window(title: &str, game_state: &GameState, renderer: &TileRenderer) -> Window {
+    let scale = if renderer.tile == 1 {
+        Scale::X8
+    } else {
+        Scale::X1
+    };
+    let (width, height) = renderer.size(&game_state.layer(0));
+    Window::new(
+        &window_title(title, game_state, 0),
+        width,
+        height,
+        WindowOptions {
+            scale,
+            ..WindowOptions::default()
+        },
+    )
+    .unwrap()
+}
+
+// renders the per cell colours of `layer` in `window_pixels` as tiles and shows them.
+fn present(
+    window: &mut Window,
+    renderer: &TileRenderer,
+    game_state: &GameState,
+    layer: usize,
+    window_pixels: &[u32],
+    tile_pixels: &mut Vec<u32>,
+) {
+    let view = game_state.layer(layer);
+    let layer_size = view.field.len();
+    let (width, height) = renderer.size(&view);
+    tile_pixels.resize(width * height, 0);
+    renderer.render(
+        &view,
+        &window_pixels[layer * layer_size..(layer + 1) * layer_size],
+        tile_pixels,
+    );
+    window
+        .update_with_buffer(tile_pixels, width, height)
+        .unwrap();
+}
+
+// the window title names the layer being shown on 3d boards.
+fn window_title(title: &str, game_state: &GameState, layer: usize) -> String {
+    if game_state.depth > 1 {
+        format!("{} - layer {} of {}", title, layer + 1, game_state.depth)
+    } else {
+        title.to_string()
+    }
+}
+
+// steps through the layers of a 3d board with page up and page down.
+fn change_layer(window: &mut Window, title: &str, game_state: &GameState, layer: &mut usize) {
+    let previous = *layer;
+    if window.is_key_pressed(Key::PageUp, KeyRepeat::Yes) {
+        *layer = layer.saturating_sub(1);
+    }
+    if window.is_key_pressed(Key::PageDown, KeyRepeat::Yes) {
+        *layer = (*layer + 1).min(game_state.depth - 1);
+    }
+    if *layer != previous {
+        window.set_title(&window_title(title, game_state, *layer));
+    }
+}
+
+fn print_step(player: &ReplayPlayer, step: Option<&ReplayStep>) {
+    if let Some(step) = step {
+        println!(
+            "step {}/{}: {:?} from {} at {}ms",
+            player.position(),
+            player.len(),
+            step.event,
+            step.source,
+            step.elapsed_ms
+        );
+    }
+}
+
+fn run_replay(path: &Path, opt: &Opt) {
+    let replay = Replay::load(path).unwrap();
+    let mut player = ReplayPlayer::new(replay).unwrap();
+
+    if opt.silence {
+        print!("{}", player.game_state());
+        for line in std::io::stdin().lock().lines() {
+            match line.unwrap().trim() {
+                "" | "n" => {
+                    let step = player.step_forward().cloned();
+                    print_step(&player, step.as_ref());
+                }
+                "p" => {
+                    let step = player.step_backward().cloned();
+                    print_step(&player, step.as_ref());
                 }
-                let neighbor_cell = game_state.at(neighbor.0, neighbor.1).unwrap();
-                if ungrouped_cells.contains(neighbor) {
-                    match neighbor_cell.visibility {
-                        CellVisibility::Unknown => {
-                            groups[group_idx].insert(*neighbor);
-                            check_queue.push(*neighbor);
-                            ungrouped_cells.remove(neighbor);
-                        }
-                        _ => {}
-                    }
+                "g" => {
+                    player.rewind_before_last("educated_guess");
+                    println!("rewound to step {}", player.position());
                 }
+                "q" => break,
+                _ => println!(
+                    "commands: n (next step), p (previous step), g (back to before the last guess), q (quit)"
+                ),
             }
+            print!("{}", player.game_state());
         }
-        // check queue must be empty now, so continue to the next ungrouped cell and start a new group.
+        return;
+    }
 
-        if ungrouped_cells.len() > 0 {
-            group_idx += 1;
-            groups.push(HashSet::new());
+    let renderer = TileRenderer::new(opt.tile);
+    let mut window = open_window("Minesweeper replay", player.game_state(), &renderer);
+    window.limit_update_rate(Some(std::time::Duration::from_micros(1000000 / 60)));
+    let mut window_pixels = vec![0u32; player.game_state().field.len()];
+    let mut tile_pixels = Vec::new();
+    let mut layer = 0;
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        change_layer(
+            &mut window,
+            "Minesweeper replay",
+            player.game_state(),
+            &mut layer,
+        );
+        if window.is_key_pressed(Key::Right, KeyRepeat::Yes) {
+            let step = player.step_forward().cloned();
+            print_step(&player, step.as_ref());
         }
+        if window.is_key_pressed(Key::Left, KeyRepeat::Yes) {
+            let step = player.step_backward().cloned();
+            print_step(&player, step.as_ref());
+        }
+        if window.is_key_pressed(Key::G, KeyRepeat::No) {
+            player.rewind_before_last("educated_guess");
+            println!("rewound to step {}", player.position());
+        }
+        draw(player.game_state(), &mut window_pixels);
+        present(
+            &mut window,
+            &renderer,
+            player.game_state(),
+            layer,
+            &window_pixels,
+            &mut tile_pixels,
+        );
     }
-    let remaining_mines = game_state.remaining_mines();
+}
 
-    println!(
-        "partitioned {} bombs into {} unknown_cells: {} groups total, {:?} distribution",
-        remaining_mines,
-        unknown_cells.len(),
-        groups.len(),
-        groups.iter().map(|e| e.len()).collect::<Vec<usize>>()
-    );
-    // let remaining_mines_float = game_state.remaining_mines() as f32;
-    let mut histogram = HashMap::new();
-    for (x, y) in unknown_cells.iter() {
-        histogram.insert(x + y * width, 0usize);
+// the event for flagging `pos` by hand. flags are added until the cell can't hold any more mines, and
+// then taken away again, so a cell that holds one mine toggles its flag.
+fn flag_event(game_state: &GameState, pos: (usize, usize), take_away: bool) -> Event {
+    match game_state.at(pos.0, pos.1).map(|c| c.visibility.flags()) {
+        Some(flags) if flags > 0 && (take_away || flags >= game_state.mines_per_cell) => {
+            Event::Unflag { pos }
+        }
+        _ => Event::Flag { pos },
     }
+}
 
-    let empty_iter: Vec<Vec<usize>> = vec![vec![]];
-    let iter: Box<dyn Iterator<Item = Vec<usize>>>;
-    if groups.len() == 1 {
-        iter = Box::new(empty_iter.iter().cloned());
-    } else {
-        iter = Box::new(CombinationIterator::new(remaining_mines, groups.len() - 1));
-    }
-
-    let search_scale = (ramanujan_approximation(remaining_mines as f32)
-        - ramanujan_approximation(groups.len() as f32 - 1.0)
-        - ramanujan_approximation(1.0 + remaining_mines as f32 - groups.len() as f32))
-        / 10.0f32.ln();
-    if search_scale < 3.0 {
-        println!("searching partitions and combinations.");
-        let mut collected = iter.collect::<Vec<_>>();
-        let collected_hashmaps: HashMap<_, _> = collected
-            .par_iter_mut()
-            .map(|partition| {
-                partition.insert(0, 0);
-                partition.push(remaining_mines);
-
-                let mine_counts: Vec<_> = partition.windows(2).map(|w| w[1] - w[0]).collect();
-                if groups
-                    .iter()
-                    .enumerate()
-                    .any(|(i, e)| mine_counts[i] > e.len())
-                {
-                    return HashMap::new();
-                }
-                // println!("");
-                let mut local_histogram = HashMap::new();
-                for (group_idx, group) in groups.iter().enumerate() {
-                    let remaining_mines = mine_counts[group_idx];
-                    let remaining_mines_float = remaining_mines as f32;
-                    let unknown_cells: Vec<_> = group.iter().cloned().collect();
-                    let unknown_cells_float = unknown_cells.len() as f32;
-                    let sub = unknown_cells_float - remaining_mines_float;
-
-                    // calculate order of magnitude of combinations that need to be searched.
-                    let search_scale = (ramanujan_approximation(unknown_cells_float)
-                        - ramanujan_approximation(remaining_mines_float)
-                        - ramanujan_approximation(sub))
-                        / 10.0f32.ln();
-
-                    if search_scale < 3.0 {
-                        // need to generate combinations and track valid solutions.
-                        print!(".");
-                        let mut hypothetical = game_state.clone();
-
-                        for combination in
-                            CombinationIterator::new(unknown_cells.len(), remaining_mines)
-                        {
-                            // combination is the indices into local unknown_cells
-                            let mut idx = 0;
-                            let mut last_seen = combination[idx];
-                            for (i, (x, y)) in unknown_cells.iter().enumerate() {
-                                if i == last_seen {
-                                    idx += 1;
-                                    if idx < combination.len() {
-                                        last_seen = combination[idx];
-                                    }
-
-                                    hypothetical.at_mut(*x, *y).unwrap().state = CellState::Mine;
-                                    continue;
-                                } else {
-                                    hypothetical.at_mut(*x, *y).unwrap().state = CellState::Empty;
-                                }
-                            }
-                            if game_state.validate(&hypothetical) {
-                                // if game_state and hypothetical were compatible, it means that either state could have resulted in the current visible appearance.
-                                // for each bomb position in the hypothetical, add 1 to its position in the histogram
-
-                                for idx in combination.iter() {
-                                    let cell = unknown_cells[*idx];
-                                    *local_histogram.entry(cell.1 * width + cell.0).or_insert(0) +=
-                                        1;
-                                }
-                            }
-                        }
-                    } else {
-                        print!("#");
-                        for (i, cell) in unknown_cells.iter().enumerate() {
-                            *local_histogram
-                                .entry(cell.1 * width + cell.0)
-                                .or_insert(0usize) += 1;
-                        }
-                    }
-                }
-                local_histogram
-            })
-            .reduce(
-                || HashMap::new(),
-                |mut a, b| {
-                    b.iter().for_each(|e| *a.entry(*e.0).or_insert(0) += e.1);
-                    a
-                },
-            );
-        // fold parallel hashmaps into main histogram
-        collected_hashmaps
-            .iter()
-            .for_each(|e| *histogram.entry(*e.0).or_insert(0) += e.1);
-    } else {
-        histogram.par_iter_mut().for_each(|(k, v)| *v += 1);
+// games played by hand reveal whole empty regions at once.
+fn new_play_game(opt: &Opt, rng: &mut StdRng) -> GameState {
+    let mut game_state = new_board(opt, rng.gen());
+    game_state.flood_fill = true;
+    game_state
+}
+
+fn play_rng(opt: &Opt) -> StdRng {
+    match opt.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
     }
+}
 
-    // now that the histogram has been tallied, select one of the cells with the lowest probability of being a bomb.
-    let mut augmented_histogram: Vec<(usize, usize)> =
-        histogram.iter().map(|(k, v)| (*k, *v)).collect();
-    augmented_histogram.sort_unstable_by_key(|e| e.1);
+fn run_play(opt: &Opt) {
+    let mut rng = play_rng(opt);
+    let mut game_state = new_play_game(opt, &mut rng);
 
-    if augmented_histogram.len() < 100 {
-        println!(
-            "guessed combinatorically, unknown: {}, remaining mines: {}. pdf was {:?}",
-            unknown_cells.len(),
-            game_state.remaining_mines(),
-            augmented_histogram,
-        );
-    } else {
-        println!("guessing randomly");
-    }
-
-    let index = augmented_histogram[0].0;
-    // if we have some nonzero number of cells that have been combinatorically deduced to not be mines,
-    if augmented_histogram[0].1 == 0 {
-        // add all but the 1st to a list so that they can be clicked on later without wasting additional computational effort.
-        for (idx, ct) in augmented_histogram.iter().skip(1) {
-            if *ct == 0 {
-                saved_valid_clicks.push(Event::Click {
-                    pos: (*idx % width, *idx / width),
-                });
+    let renderer = TileRenderer::new(opt.tile);
+    let mut window = open_window("Minesweeper", &game_state, &renderer);
+    window.limit_update_rate(Some(std::time::Duration::from_micros(1000000 / 60)));
+    let mut window_pixels = vec![0u32; game_state.field.len()];
+    let mut tile_pixels = Vec::new();
+    let mut layer = 0;
+
+    let buttons = [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+    let mut was_down = [false; 3];
+    let mut suggestion = Event::None;
+    // cached until the board changes, since the probabilities can be expensive to compute.
+    let mut overlay: Option<Overlay> = None;
+    let mut show_overlay = false;
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        let condition = game_state.game_condition;
+        if window.is_key_pressed(Key::N, KeyRepeat::No) {
+            game_state = new_play_game(opt, &mut rng);
+            suggestion = Event::None;
+            overlay = None;
+        }
+        if window.is_key_pressed(Key::U, KeyRepeat::Yes) {
+            game_state.undo();
+            suggestion = Event::None;
+            overlay = None;
+        }
+        if window.is_key_pressed(Key::R, KeyRepeat::Yes) {
+            game_state.redo();
+            suggestion = Event::None;
+            overlay = None;
+        }
+        if window.is_key_pressed(Key::H, KeyRepeat::No) {
+            suggestion = hint(&game_state);
+            println!("hint: {:?}", suggestion);
+        }
+        change_layer(&mut window, "Minesweeper", &game_state, &mut layer);
+        if window.is_key_pressed(Key::S, KeyRepeat::No) {
+            show_overlay = !show_overlay;
+            if show_overlay {
+                println!("overlay: blue is proven safe, dark red is a proven mine, green to red is the chance of a mine");
+            }
+        }
+
+        // act on presses rather than on held buttons.
+        let down = buttons.map(|button| window.get_mouse_down(button));
+        let pos = window
+            .get_mouse_pos(MouseMode::Discard)
+            .and_then(|(x, y)| renderer.cell_at(&game_state.layer(layer), (x as usize, y as usize)))
+            .map(|(x, y)| (x, layer * game_state.layer_height() + y));
+        if let (Some(pos), GameCondition::InProgress) = (pos, game_state.game_condition) {
+            let cell = game_state.at(pos.0, pos.1);
+            let event = if down[0] && !was_down[0] {
+                Event::Click { pos }
+            } else if down[1] && !was_down[1] {
+                let shift =
+                    window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+                flag_event(&game_state, pos, shift)
+            } else if down[2] && !was_down[2] {
+                Event::Chord { pos }
             } else {
-                break;
+                Event::None
+            };
+            if event != Event::None && cell.is_some() {
+                game_state.apply(event);
+                suggestion = Event::None;
+                overlay = None;
+            }
+        }
+        was_down = down;
+
+        if game_state.game_condition != condition {
+            match game_state.game_condition {
+                GameCondition::Won => println!("game won! press n for a new game"),
+                GameCondition::Lost => println!("game lost. press u to undo or n for a new game"),
+                GameCondition::InProgress => {}
             }
         }
-    }
 
-    if augmented_histogram[0].1 > 0 {
-        // since the chance of the picked entry being a bomb is nonzero, add 1 to the guess count to indicate that actual guesses (rather than combinatoric deductions) are being performed
-        *guess_count += 1;
+        draw(&game_state, &mut window_pixels);
+        if show_overlay {
+            overlay
+                .get_or_insert_with(|| Overlay::new(&game_state))
+                .draw(&game_state, &mut window_pixels);
+        }
+        match suggestion {
+            Event::Click { pos } => {
+                window_pixels[pos.1 * game_state.width + pos.0] = rgb_to_u32(255, 255, 0)
+            }
+            Event::Flag { pos } => {
+                window_pixels[pos.1 * game_state.width + pos.0] = rgb_to_u32(255, 0, 255)
+            }
+            _ => {}
+        }
+        present(
+            &mut window,
+            &renderer,
+            &game_state,
+            layer,
+            &window_pixels,
+            &mut tile_pixels,
+        );
     }
-    let (x, y) = (index % width, index / width);
-    drop(unknown_cells);
-    event = Event::Click { pos: (x, y) };
+}
 
-    event
+fn draw_board(
+    render_mode: RenderMode,
+    game_state: &GameState,
+    probabilities: &BTreeMap<usize, f32>,
+    saved_valid_clicks: &[Event],
+    window_pixels: &mut [u32],
+) {
+    match render_mode {
+        RenderMode::Plain => draw(game_state, window_pixels),
+        RenderMode::HeatMap => {
+            draw_heat_map(game_state, probabilities, saved_valid_clicks, window_pixels)
+        }
+    }
 }
 
-fn main() {
-    let opt = Opt::from_args();
-    let (width, height) = (opt.width, opt.height);
+// called after every applied event. frames are drawn the same way as the window, so this also
+// works with `--silence`, and the final position of every game is always captured.
+fn record_frame(
+    frames: &mut Option<FrameRecorder>,
+    game_state: &GameState,
+    opt: &Opt,
+    probabilities: &BTreeMap<usize, f32>,
+    saved_valid_clicks: &[Event],
+    window_pixels: &mut [u32],
+) {
+    let frames = match frames {
+        Some(frames) => frames,
+        None => return,
+    };
+    let game_over = game_state.game_condition != GameCondition::InProgress;
+    if frames.tick() || game_over {
+        draw_board(
+            opt.render_mode,
+            game_state,
+            probabilities,
+            saved_valid_clicks,
+            window_pixels,
+        );
+        if let Err(e) = frames.capture(game_state, window_pixels) {
+            println!("failed to record frame: {}", e);
+        }
+    }
+    if game_over {
+        if let Err(e) = frames.finish_game() {
+            println!("failed to finish recording: {}", e);
+        }
+    }
+}
 
-    let mut window = None;
+fn run_play_tui(opt: &Opt) -> std::io::Result<()> {
+    let mut rng = play_rng(opt);
+    let mut game_state = new_play_game(opt, &mut rng);
+    let mut terminal = Terminal::new()?;
+    let mut cursor = (0, 0);
+    let mut status = String::from("press ? for a hint, q to quit");
+    loop {
+        terminal.draw(&game_state, Some(cursor), &status)?;
+        let key = match terminal.poll_key(std::time::Duration::from_millis(250))? {
+            Some(key) => key,
+            None => continue,
+        };
+        if tui::move_cursor(&game_state, &mut cursor, key) {
+            continue;
+        }
+        let event = match key {
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            KeyCode::Char(' ') | KeyCode::Enter => Event::Click { pos: cursor },
+            KeyCode::Char('f') => flag_event(&game_state, cursor, false),
+            KeyCode::Char('F') => flag_event(&game_state, cursor, true),
+            KeyCode::Char('c') => Event::Chord { pos: cursor },
+            KeyCode::Char('u') => {
+                game_state.undo();
+                Event::None
+            }
+            KeyCode::Char('r') => {
+                game_state.redo();
+                Event::None
+            }
+            KeyCode::Char('n') => {
+                game_state = new_play_game(opt, &mut rng);
+                status.clear();
+                Event::None
+            }
+            KeyCode::Char('?') => {
+                let suggestion = hint(&game_state);
+                match suggestion {
+                    Event::Click { pos } | Event::Flag { pos } => cursor = pos,
+                    _ => {}
+                }
+                status = format!("hint: {:?}", suggestion);
+                Event::None
+            }
+            _ => Event::None,
+        };
+        if event != Event::None && game_state.game_condition == GameCondition::InProgress {
+            game_state.apply(event);
+            status = match game_state.game_condition {
+                GameCondition::Won => String::from("game won! press n for a new game"),
+                GameCondition::Lost => {
+                    String::from("game lost. press u to undo or n for a new game")
+                }
+                GameCondition::InProgress => String::new(),
+            };
+        }
+    }
+    Ok(())
+}
 
-    if !opt.silence {
-        window = Some(
-            Window::new(
-                "Minesweeper",
-                width,
-                height,
-                WindowOptions {
-                    scale: Scale::X8,
-                    ..WindowOptions::default()
-                },
-            )
-            .unwrap(),
-        );
+// fill in the board size and mine count from `--difficulty` or `--density`. `matches` tells the options
+// that were given apart from their defaults, since those can't be combined with a preset.
+fn apply_difficulty(opt: &mut Opt, matches: &structopt::clap::ArgMatches) -> Result<(), String> {
+    let given: Vec<&str> = [
+        ("width", "-w"),
+        ("height", "-h"),
+        ("num-bombs", "--num-bombs"),
+        ("density", "--density"),
+    ]
+    .iter()
+    .filter(|(name, _)| matches.occurrences_of(name) > 0)
+    .map(|(_, flag)| *flag)
+    .collect();
+    if let Some((width, height, num_bombs)) = opt.difficulty.board() {
+        if let Some(flag) = given.first() {
+            return Err(format!(
+                "--difficulty {} sets the board size and mine count, so it can't be used with {}",
+                opt.difficulty, flag
+            ));
+        }
+        opt.width = width;
+        opt.height = height;
+        opt.num_bombs = num_bombs;
+    }
+    if let Some(density) = opt.density {
+        if given.contains(&"--num-bombs") {
+            return Err(String::from(
+                "--density and --num-bombs can't be used together",
+            ));
+        }
+        if !(0.0..=opt.mines_per_cell as f32).contains(&density) {
+            return Err(format!(
+                "--density has to be between 0 and {}",
+                opt.mines_per_cell
+            ));
+        }
+        let cells = opt.width * opt.height * opt.depth;
+        opt.num_bombs = (density * cells as f32).round() as usize;
     }
+    Ok(())
+}
 
-    let frame_micros = 1000000.0 / 144.0;
-    &mut window
-        .as_mut()
-        .map(|w| w.limit_update_rate(Some(std::time::Duration::from_micros(frame_micros as u64))));
+// hexagons need tiles of at least 4 pixels, and 8 is the smallest that also fits the digits.
+fn apply_tile(opt: &mut Opt, matches: &structopt::clap::ArgMatches) -> Result<(), String> {
+    if opt.neighborhood != Neighborhood::Hex {
+        return Ok(());
+    }
+    if matches.occurrences_of("tile") == 0 {
+        opt.tile = 8;
+    } else if opt.tile < 4 {
+        return Err(String::from(
+            "--neighborhood hex draws hexagons, which need a --tile of at least 4",
+        ));
+    }
+    Ok(())
+}
 
-    let mut game_state = GameState::new(width, height, opt.num_bombs);
-    let mut window_pixels = vec![0u32; width * height];
+// combinations of options that can't make a board.
+fn check_options(opt: &Opt) -> Result<(), String> {
+    if opt.depth == 0 {
+        return Err(String::from("--depth has to be at least 1"));
+    }
+    if opt.neighborhood.reach(opt.depth) > MAX_NEIGHBORS {
+        return Err(format!(
+            "{} has more than {} neighbors with a depth of {}",
+            opt.neighborhood, MAX_NEIGHBORS, opt.depth
+        ));
+    }
+    opt.neighborhood.check_topology(opt.topology, opt.height)?;
+    if opt.mines_per_cell == 0 {
+        return Err(String::from("--mines-per-cell has to be at least 1"));
+    }
+    // the layers are stacked vertically, like `random_board` does.
+    GameState::check_dimensions(
+        opt.width,
+        opt.height.saturating_mul(opt.depth),
+        opt.num_bombs,
+        opt.mines_per_cell,
+    )
+    .map_err(|e| e.to_string())?;
+    // a cell surrounded by full cells has to show a number that the board can draw.
+    if opt.neighborhood.reach(opt.depth) * opt.mines_per_cell > MAX_NEIGHBORS {
+        return Err(format!(
+            "numbers around cells with {} mines go past {} with {}",
+            opt.mines_per_cell, MAX_NEIGHBORS, opt.neighborhood
+        ));
+    }
+    Ok(())
+}
 
+fn main() {
+    let matches = Opt::clap().get_matches();
+    let mut opt = Opt::from_clap(&matches);
+    let configured =
+        apply_difficulty(&mut opt, &matches).and_then(|_| apply_tile(&mut opt, &matches));
+    if let Err(e) = configured.and_then(|_| check_options(&opt)) {
+        structopt::clap::Error::with_description(&e, structopt::clap::ErrorKind::InvalidValue)
+            .exit();
+    }
+    if let Some(path) = &opt.replay {
+        run_replay(path, &opt);
+        return;
+    }
+    if let Some(addr) = &opt.serve {
+        let addr = addr.as_deref().unwrap_or("127.0.0.1:8080");
+        let defaults = GameOptions {
+            width: opt.width,
+            height: opt.height,
+            mines: opt.num_bombs,
+            seed: opt.seed,
+            topology: opt.topology,
+            neighborhood: opt.neighborhood.clone(),
+            depth: opt.depth,
+            mines_per_cell: opt.mines_per_cell,
+        };
+        let server = GameServer::bind(addr, defaults).unwrap_or_else(|e| {
+            structopt::clap::Error::with_description(
+                &e.to_string(),
+                structopt::clap::ErrorKind::InvalidValue,
+            )
+            .exit()
+        });
+        println!("serving games on http://{}", server.local_addr());
+        server.run();
+        return;
+    }
+    if opt.bot {
+        let mut rng = play_rng(&opt);
+        let mut session = BotSession::new(|| new_play_game(&opt, &mut rng));
+        session
+            .serve(std::io::stdin().lock(), std::io::stdout().lock())
+            .unwrap();
+        return;
+    }
+    if opt.play && opt.tui {
+        run_play_tui(&opt).unwrap();
+        return;
+    }
+    if opt.play {
+        run_play(&opt);
+        return;
+    }
+    // boards without guesses are judged by the solver, so the pool has to be set up before the first one.
     rayon::ThreadPoolBuilder::new()
         .num_threads(opt.threads)
         .build_global()
         .unwrap();
+    let mut rng = match opt.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let seed = rng.gen();
+    let mut game_state = new_board(&opt, seed);
+
+    let renderer = TileRenderer::new(opt.tile);
+    let mut window = None;
+    let mut terminal = None;
+
+    if opt.tui && !opt.silence {
+        terminal = Some(Terminal::new().unwrap());
+    } else if !opt.silence {
+        window = Some(open_window("Minesweeper", &game_state, &renderer));
+    }
+
+    let frame_micros = 1000000.0 / 144.0;
+    if let Some(w) = window.as_mut() {
+        w.limit_update_rate(Some(std::time::Duration::from_micros(frame_micros as u64)));
+    }
+
+    let mut window_pixels = vec![0u32; game_state.field.len()];
+    let mut tile_pixels = Vec::new();
+    let mut layer = 0;
+
     // let (x, y) = game_state.random_xy_2();
     // game_state.click(x, y);
     // let mut one_off = true;
-    let mut solver = Solver::new();
+    let mut solver = if opt.no_guess { judge() } else { Solver::new() };
+    solver.init(&game_state);
     let mut guess_count = 0;
     let mut frame = 0;
     let framerule = opt.skip;
     let mut saved_valid_clicks = Vec::new();
+    // mine probabilities from the last guess, for the heat map.
+    let mut probabilities = BTreeMap::new();
     let mut wins = (0, 0);
+    let mut capture = opt
+        .capture_dir
+        .clone()
+        .map(|dir| LossCapture::new(dir, &opt));
+    let mut recording = opt
+        .record_dir
+        .clone()
+        .map(|dir| ReplayCapture::new(dir, &game_state, seed));
+    let mut frames = opt.frame_dir.clone().map(|dir| {
+        FrameRecorder::new(
+            dir,
+            opt.frame_format,
+            framerule,
+            TileRenderer::new(opt.tile),
+        )
+        .unwrap()
+    });
 
     'outer: loop {
-        if let Some(w) = &window {
-            if !(w.is_open() && !w.is_key_down(Key::Escape)) {
+        if let Some(w) = &mut window {
+            if !w.is_open() || w.is_key_down(Key::Escape) {
+                break;
+            }
+            change_layer(w, "Minesweeper", &game_state, &mut layer);
+        }
+        if let Some(terminal) = &terminal {
+            if let Some(KeyCode::Char('q') | KeyCode::Esc) =
+                terminal.poll_key(std::time::Duration::ZERO).unwrap()
+            {
                 break;
             }
         }
         frame += 1;
         // draw phase
-        if !opt.silence {
-            for (i, cell) in game_state.field.iter().enumerate() {
-                let (x, y) = (i % width, i / width);
-                match cell {
-                    Cell {
-                        visibility: CellVisibility::Unknown,
-                        ..
-                    } => {
-                        window_pixels[y * width + x] = rgb_to_u32(128, 128, 128);
-                    }
-                    Cell {
-                        visibility: CellVisibility::Empty(neighbors),
-                        ..
-                    } => {
-                        // println!("blue is {}", (*neighbors as f32 * 256.0 / 8.0));
-
-                        window_pixels[y * width + x] = match neighbors {
-                            0 => rgb_to_u32(0, 0, 0),
-                            1 => rgb_to_u32(0, 64, 64),
-                            2 => rgb_to_u32(0, 64, 127),
-                            3 => rgb_to_u32(80, 127, 255),
-                            4 => rgb_to_u32(80, 127, 0),
-                            5 => rgb_to_u32(80, 180, 127),
-                            6 => rgb_to_u32(160, 180, 180),
-                            7 => rgb_to_u32(160, 255, 255),
-                            _ => rgb_to_u32(255, 255, 255),
-                        };
-                    }
-                    Cell {
-                        visibility: CellVisibility::Flagged,
-                        ..
-                    } => {
-                        window_pixels[y * width + x] = rgb_to_u32(255, 0, 0);
-                    }
-                }
-            }
+        if window.is_some() {
+            draw_board(
+                opt.render_mode,
+                &game_state,
+                &probabilities,
+                &saved_valid_clicks,
+                &mut window_pixels,
+            );
         }
 
         // ai update and gamestate progression phase
 
-        let mut events = solver.next_clicks(&game_state);
-        events.append(&mut saved_valid_clicks);
-        for event in events.iter() {
-            match event {
-                Event::Flag { pos } => game_state.flag(pos.0, pos.1),
-                Event::Click { pos } => game_state.click(pos.0, pos.1),
-
-                Event::None => {}
+        let mut events = solver.next_moves(&game_state);
+        events.extend(
+            saved_valid_clicks
+                .drain(..)
+                .map(|e| (e, "saved_valid_clicks")),
+        );
+        for (event, source) in events.iter() {
+            let outcome = game_state.apply(*event);
+            if let Some(recording) = &mut recording {
+                recording.record(*event, source);
             }
+            record_frame(
+                &mut frames,
+                &game_state,
+                &opt,
+                &probabilities,
+                &saved_valid_clicks,
+                &mut window_pixels,
+            );
 
             if check_and_restart_game(
                 &mut game_state,
@@ -485,23 +1065,47 @@ fn main() {
                 &mut saved_valid_clicks,
                 &mut guess_count,
                 &mut wins,
-                opt.num_bombs,
+                &mut rng,
+                &mut capture,
+                &mut recording,
                 &opt,
+                false,
             ) {
+                probabilities.clear();
                 continue 'outer;
             }
-            solver.update(&game_state, *event);
+            solver.update_from(&game_state, &outcome);
         }
 
-        if events.len() == 0 {
-            let event = educated_guess(&mut game_state, &mut guess_count, &mut saved_valid_clicks);
-
-            match event {
-                Event::Flag { pos } => game_state.flag(pos.0, pos.1),
-                Event::Click { pos } => game_state.click(pos.0, pos.1),
-
-                Event::None => {}
+        if events.is_empty() {
+            let tally = tally(&game_state);
+            if opt.render_mode == RenderMode::HeatMap {
+                probabilities = tally.probabilities(game_state.mines_left());
             }
+            if !terminal_ui(&opt) {
+                println!("{}", describe_tally(&game_state, &tally));
+            }
+            let event = guess_from_tally(
+                &game_state,
+                &tally,
+                &mut guess_count,
+                &mut saved_valid_clicks,
+            );
+            // nothing to reveal and no mines left to flag means some flag is wrong, and since the
+            // solver never takes flags away the game can't be won. it's started over instead.
+            let stuck = event == Event::None;
+            let outcome = game_state.apply(event);
+            if let Some(recording) = &mut recording {
+                recording.record(event, "educated_guess");
+            }
+            record_frame(
+                &mut frames,
+                &game_state,
+                &opt,
+                &probabilities,
+                &saved_valid_clicks,
+                &mut window_pixels,
+            );
 
             if check_and_restart_game(
                 &mut game_state,
@@ -509,20 +1113,36 @@ fn main() {
                 &mut saved_valid_clicks,
                 &mut guess_count,
                 &mut wins,
-                opt.num_bombs,
+                &mut rng,
+                &mut capture,
+                &mut recording,
                 &opt,
+                stuck,
             ) {
+                probabilities.clear();
                 continue 'outer;
             }
-            solver.update(&game_state, event);
+            solver.update_from(&game_state, &outcome);
         }
 
         // window update
         if let Some(window) = &mut window {
             if frame % framerule == 0 {
-                window
-                    .update_with_buffer(&window_pixels, width, height)
-                    .unwrap();
+                present(
+                    window,
+                    &renderer,
+                    &game_state,
+                    layer,
+                    &window_pixels,
+                    &mut tile_pixels,
+                );
+                frame %= framerule;
+            }
+        }
+        if let Some(terminal) = &mut terminal {
+            if frame % framerule == 0 {
+                let status = format!("{} of {} games won, q to quit", wins.0, wins.1);
+                terminal.draw(&game_state, None, &status).unwrap();
                 frame %= framerule;
             }
         }
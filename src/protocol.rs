@@ -0,0 +1,322 @@
+// newline delimited json protocol for playing games from another process, e.g. a bot written in
+// another language. every message is a single json object on its own line.
+//
+// the engine starts by sending an observation of a new game, and answers every command with
+// exactly one response.
+//
+// commands (bot to engine):
+//   {"command": "click", "x": 3, "y": 4}
+//   {"command": "flag", "x": 3, "y": 4}
+//   {"command": "unflag", "x": 3, "y": 4}
+//   {"command": "chord", "x": 3, "y": 4}
+//   {"command": "observe"}
+//   {"command": "new_game"}
+//
+// responses (engine to bot):
+//   {"type": "observation", "width": 9, "height": 9, "mines": 10, "remaining_mines": 10,
+//    "mines_per_cell": 1, "topology": {"wrap_x": false, "wrap_y": false}, "neighborhood": "moore",
+//    "depth": 1, "condition": "in_progress", "board": ["#########", ...]}
+//   {"type": "error", "message": "..."}
+//
+// `condition` is one of `in_progress`, `won` or `lost`. `board` has one string per row, using the
+// symbols of the text format: `#` unknown, `F` flagged, `.`, `1`-`9` and `a`-`z` revealed. once a
+// game is lost, its mines are shown as `*`. a move that is not legal in the current position, or a
+// line that is not a valid command, gets an error and leaves the game unchanged.
+// `remaining_mines` is the number of mines minus the number of flags, right or wrong. a game is won by
+// revealing every safe cell, or by flagging every mine without any wrong flags.
+// when `mines_per_cell` is above 1, a cell can hold that many mines and its number counts all of them.
+// each `flag` then adds one flag to a cell and each `unflag` takes one away.
+// `topology` says which edges of the board wrap around, so that cells on one edge neighbor the
+// cells on the opposite edge.
+// `neighborhood` says which cells a number counts: `moore` for the 8 surrounding cells, `hex` for the
+// 6 surrounding cells of a hex grid whose odd rows are shifted right by half a cell, or
+// {"kernel": [[dx, dy], ...]} for any other set of offsets.
+// a board with a `depth` above 1 is 3d. its layers are stacked in `board`, each `height / depth` rows
+// tall, and a cell also neighbors the cells of the layers directly above and below it.
+
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::*;
+use crate::text_format::number_symbol;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    Click { x: usize, y: usize },
+    Flag { x: usize, y: usize },
+    Unflag { x: usize, y: usize },
+    Chord { x: usize, y: usize },
+    Observe,
+    NewGame,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    InProgress,
+    Won,
+    Lost,
+}
+
+impl From<GameCondition> for Condition {
+    fn from(condition: GameCondition) -> Self {
+        match condition {
+            GameCondition::InProgress => Condition::InProgress,
+            GameCondition::Won => Condition::Won,
+            GameCondition::Lost => Condition::Lost,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    pub width: usize,
+    pub height: usize,
+    pub mines: usize,
+    pub remaining_mines: usize,
+    pub mines_per_cell: usize,
+    pub topology: Topology,
+    pub neighborhood: Neighborhood,
+    pub depth: usize,
+    pub condition: Condition,
+    pub board: Vec<String>,
+}
+
+impl From<&GameState> for Observation {
+    fn from(game_state: &GameState) -> Self {
+        let lost = game_state.game_condition == GameCondition::Lost;
+        let board = game_state
+            .field
+            .chunks(game_state.width)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match cell.visibility {
+                        CellVisibility::Unknown if lost && cell.state.mines() > 0 => '*',
+                        CellVisibility::Unknown => '#',
+                        CellVisibility::Flagged(_) => 'F',
+                        CellVisibility::Empty(n) => number_symbol(n),
+                    })
+                    .collect()
+            })
+            .collect();
+        Observation {
+            width: game_state.width,
+            height: game_state.height,
+            mines: game_state.bomb_count,
+            // counting every flag, so that wrong flags can't be told apart from correct ones.
+            remaining_mines: game_state.mines_left(),
+            mines_per_cell: game_state.mines_per_cell,
+            topology: game_state.topology,
+            neighborhood: game_state.neighborhood.clone(),
+            depth: game_state.depth,
+            condition: game_state.game_condition.into(),
+            board,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Observation(Observation),
+    Error { message: String },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProtocolError {
+    InvalidCommand(String),
+    OutOfBounds { pos: (usize, usize) },
+    GameOver,
+    IllegalMove { event: Event, reason: &'static str },
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::InvalidCommand(e) => write!(f, "invalid command: {}", e),
+            ProtocolError::OutOfBounds { pos } => {
+                write!(f, "({}, {}) is outside of the board", pos.0, pos.1)
+            }
+            ProtocolError::GameOver => {
+                write!(f, "the game is over, send new_game to start another")
+            }
+            ProtocolError::IllegalMove { event, reason } => {
+                write!(f, "illegal move {:?}: {}", event, reason)
+            }
+        }
+    }
+}
+
+impl Error for ProtocolError {}
+
+impl From<GameError> for ProtocolError {
+    fn from(e: GameError) -> Self {
+        match e {
+            GameError::OutOfBounds { pos } => ProtocolError::OutOfBounds { pos },
+            GameError::GameOver => ProtocolError::GameOver,
+            GameError::IllegalMove { event, reason } => {
+                ProtocolError::IllegalMove { event, reason }
+            }
+            e => ProtocolError::InvalidCommand(e.to_string()),
+        }
+    }
+}
+
+// one game at a time, driven by commands. `new_game` is called for every game that is started.
+pub struct BotSession<F: FnMut() -> GameState> {
+    game_state: GameState,
+    new_game: F,
+}
+
+impl<F: FnMut() -> GameState> BotSession<F> {
+    pub fn new(mut new_game: F) -> Self {
+        BotSession {
+            game_state: new_game(),
+            new_game,
+        }
+    }
+
+    pub fn game_state(&self) -> &GameState {
+        &self.game_state
+    }
+
+    pub fn observe(&self) -> Response {
+        Response::Observation(Observation::from(&self.game_state))
+    }
+
+    pub fn handle(&mut self, command: Command) -> Result<Response, ProtocolError> {
+        let event = match command {
+            Command::Click { x, y } => Event::Click { pos: (x, y) },
+            Command::Flag { x, y } => Event::Flag { pos: (x, y) },
+            Command::Unflag { x, y } => Event::Unflag { pos: (x, y) },
+            Command::Chord { x, y } => Event::Chord { pos: (x, y) },
+            Command::Observe => Event::None,
+            Command::NewGame => {
+                self.game_state = (self.new_game)();
+                Event::None
+            }
+        };
+        self.game_state.try_apply(event)?;
+        Ok(self.observe())
+    }
+
+    // the response to one line of input. errors are turned into error responses.
+    pub fn handle_line(&mut self, line: &str) -> Response {
+        serde_json::from_str(line)
+            .map_err(|e| ProtocolError::InvalidCommand(e.to_string()))
+            .and_then(|command| self.handle(command))
+            .unwrap_or_else(|e| Response::Error {
+                message: e.to_string(),
+            })
+    }
+
+    // speaks the protocol until `reader` runs out of lines. blank lines are ignored.
+    pub fn serve<R: BufRead, W: Write>(&mut self, reader: R, mut writer: W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{}",
+            serde_json::to_string(&self.observe()).unwrap()
+        )?;
+        writer.flush()?;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = self.handle_line(&line);
+            writeln!(writer, "{}", serde_json::to_string(&response).unwrap())?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let mut session = BotSession::new(|| "3 2 1\n*##\n###".parse().unwrap());
+        let input = [
+            r#"{"command": "click", "x": 2, "y": 0}"#,
+            r#"{"command": "click", "x": 2, "y": 0}"#,
+            r#"{"command": "flag", "x": 5, "y": 0}"#,
+            r#"{"command": "jump"}"#,
+            "",
+            r#"{"command": "flag", "x": 0, "y": 0}"#,
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        session.serve(input.as_bytes(), &mut output).unwrap();
+        let responses: Vec<Response> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 6);
+
+        match &responses[1] {
+            Response::Observation(observation) => {
+                assert_eq!(observation.board, vec!["##.", "###"]);
+                assert_eq!(observation.topology, Topology::BOUNDED);
+                assert_eq!(observation.neighborhood, Neighborhood::Moore);
+                assert_eq!(observation.depth, 1);
+                assert_eq!(observation.condition, Condition::InProgress);
+            }
+            response => panic!("unexpected response {:?}", response),
+        }
+        for response in &responses[2..5] {
+            assert!(matches!(response, Response::Error { .. }));
+        }
+        match &responses[5] {
+            Response::Observation(observation) => {
+                assert_eq!(observation.remaining_mines, 0);
+                assert_eq!(observation.condition, Condition::Won);
+            }
+            response => panic!("unexpected response {:?}", response),
+        }
+        assert_eq!(
+            session.handle(Command::Click { x: 1, y: 1 }),
+            Err(ProtocolError::GameOver)
+        );
+        session.handle(Command::NewGame).unwrap();
+        assert_eq!(
+            session.handle(Command::Chord { x: 0, y: 1 }),
+            Err(ProtocolError::IllegalMove {
+                event: Event::Chord { pos: (0, 1) },
+                reason: "the cell is not revealed"
+            })
+        );
+    }
+
+    #[test]
+    fn test_flagging_everything_does_not_win() {
+        let mut session = BotSession::new(|| GameState::from_seed(9, 9, 10, 1));
+        for y in 0..9 {
+            for x in 0..9 {
+                session.handle(Command::Flag { x, y }).unwrap();
+            }
+        }
+        assert_eq!(
+            session.game_state().game_condition,
+            GameCondition::InProgress
+        );
+        match session.observe() {
+            Response::Observation(observation) => assert_eq!(observation.remaining_mines, 0),
+            response => panic!("unexpected response {:?}", response),
+        }
+
+        // revealing every safe cell wins without flags.
+        let mut session = BotSession::new(|| "3 1 1\n*##".parse().unwrap());
+        session.handle(Command::Click { x: 2, y: 0 }).unwrap();
+        session.handle(Command::Click { x: 1, y: 0 }).unwrap();
+        assert_eq!(session.game_state().game_condition, GameCondition::Won);
+    }
+}
@@ -0,0 +1,426 @@
+// drawing boards into a minifb style pixel buffer, either one pixel per cell or, with a
+// `TileRenderer`, one tile of pixels per cell.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::game::*;
+use crate::guess::mine_probabilities;
+use crate::solver::{Deductions, Solver};
+
+pub fn rgb_to_u32(r: u8, g: u8, b: u8) -> u32 {
+    ((r as u32) << 16) | ((g as u32) << 8) | (b as u32)
+}
+
+pub fn draw(game_state: &GameState, window_pixels: &mut [u32]) {
+    let width = game_state.width;
+    for (i, cell) in game_state.field.iter().enumerate() {
+        let (x, y) = (i % width, i / width);
+        match cell {
+            Cell {
+                visibility: CellVisibility::Unknown,
+                state,
+            } => {
+                // mines are only shown once the game has been lost.
+                window_pixels[y * width + x] =
+                    if state.mines() > 0 && game_state.game_condition == GameCondition::Lost {
+                        rgb_to_u32(255, 160, 0)
+                    } else {
+                        rgb_to_u32(128, 128, 128)
+                    };
+            }
+            Cell {
+                visibility: CellVisibility::Empty(neighbors),
+                ..
+            } => {
+                // println!("blue is {}", (*neighbors as f32 * 256.0 / 8.0));
+
+                window_pixels[y * width + x] = match neighbors {
+                    0 => rgb_to_u32(0, 0, 0),
+                    1 => rgb_to_u32(0, 64, 64),
+                    2 => rgb_to_u32(0, 64, 127),
+                    3 => rgb_to_u32(80, 127, 255),
+                    4 => rgb_to_u32(80, 127, 0),
+                    5 => rgb_to_u32(80, 180, 127),
+                    6 => rgb_to_u32(160, 180, 180),
+                    7 => rgb_to_u32(160, 255, 255),
+                    _ => rgb_to_u32(255, 255, 255),
+                };
+            }
+            Cell {
+                visibility: CellVisibility::Flagged(_),
+                ..
+            } => {
+                window_pixels[y * width + x] = rgb_to_u32(255, 0, 0);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    // fixed colours for every kind of cell.
+    Plain,
+    // unknown cells are shaded by their chance of being a mine.
+    HeatMap,
+}
+
+impl FromStr for RenderMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(RenderMode::Plain),
+            "heat-map" => Ok(RenderMode::HeatMap),
+            _ => Err(format!(
+                "unknown render mode {:?}, expected `plain` or `heat-map`",
+                s
+            )),
+        }
+    }
+}
+
+// green for certainly safe through yellow to red for a certain mine.
+pub fn probability_color(p: f32) -> u32 {
+    let p = p.clamp(0.0, 1.0);
+    if p < 0.5 {
+        rgb_to_u32((p * 2.0 * 255.0) as u8, 255, 0)
+    } else {
+        rgb_to_u32(255, ((1.0 - p) * 2.0 * 255.0) as u8, 0)
+    }
+}
+
+// the solver's knowledge of a position: what it can prove, and how likely every other unknown cell is to be a mine.
+pub struct Overlay {
+    pub deductions: Deductions,
+    pub probabilities: BTreeMap<usize, f32>,
+}
+
+impl Overlay {
+    // computed from the board alone, so no moves are applied to `game_state`.
+    pub fn new(game_state: &GameState) -> Self {
+        let deductions = Solver::deductions(game_state);
+        let probabilities = if game_state.game_condition == GameCondition::InProgress {
+            mine_probabilities(game_state)
+        } else {
+            BTreeMap::new()
+        };
+        Overlay {
+            deductions,
+            probabilities,
+        }
+    }
+
+    // draws over the unknown cells of a buffer that `draw` has already filled.
+    pub fn draw(&self, game_state: &GameState, window_pixels: &mut [u32]) {
+        let width = game_state.width;
+        for (idx, p) in self.probabilities.iter() {
+            window_pixels[*idx] = probability_color(*p);
+        }
+        for (x, y) in self.deductions.safe.iter() {
+            window_pixels[y * width + x] = rgb_to_u32(0, 200, 255);
+        }
+        for (x, y) in self.deductions.mines.iter() {
+            window_pixels[y * width + x] = rgb_to_u32(128, 0, 0);
+        }
+    }
+}
+
+// like `draw`, but unknown cells are shaded by `probabilities` (indexed by cell) and the clicks in
+// `saved_valid_clicks` are highlighted. cells without a probability keep their plain colour.
+pub fn draw_heat_map(
+    game_state: &GameState,
+    probabilities: &BTreeMap<usize, f32>,
+    saved_valid_clicks: &[Event],
+    window_pixels: &mut [u32],
+) {
+    draw(game_state, window_pixels);
+    if game_state.game_condition == GameCondition::Lost {
+        return;
+    }
+    for (idx, p) in probabilities.iter() {
+        if game_state.field[*idx].visibility == CellVisibility::Unknown {
+            window_pixels[*idx] = probability_color(*p);
+        }
+    }
+    for event in saved_valid_clicks {
+        if let Event::Click { pos: (x, y) } = event {
+            if game_state.at(*x, *y).map(|c| c.visibility) == Some(CellVisibility::Unknown) {
+                window_pixels[y * game_state.width + x] = rgb_to_u32(0, 200, 255);
+            }
+        }
+    }
+}
+
+// 3x5 bitmaps for the digits 1 to 8, one row per byte with the leftmost pixel in the high bit.
+const DIGIT_GLYPHS: [[u8; 5]; 8] = [
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+];
+// 5x5 bitmaps.
+const FLAG_GLYPH: [u8; 5] = [0b01100, 0b01110, 0b01100, 0b01000, 0b11100];
+const MINE_GLYPH: [u8; 5] = [0b00100, 0b01110, 0b11111, 0b01110, 0b00100];
+
+fn digit_color(n: usize) -> u32 {
+    match n {
+        1 => rgb_to_u32(0, 0, 255),
+        2 => rgb_to_u32(0, 128, 0),
+        3 => rgb_to_u32(255, 0, 0),
+        4 => rgb_to_u32(0, 0, 128),
+        5 => rgb_to_u32(128, 0, 0),
+        6 => rgb_to_u32(0, 128, 128),
+        7 => rgb_to_u32(0, 0, 0),
+        _ => rgb_to_u32(96, 96, 96),
+    }
+}
+
+const GRID_COLOR: u32 = 0x40_40_40;
+
+// whether the center of pixel (`tx`, `ty`) of a `tile`x`tile` box lies in the pointy topped hexagon
+// filling the box. distances are doubled to stay in integers.
+fn in_hex(tile: usize, tx: usize, ty: usize) -> bool {
+    let (tile, tx, ty) = (tile as isize, tx as isize, ty as isize);
+    (2 * tx + 1 - tile).abs() <= tile.min(4 * ty + 2).min(4 * tile - 4 * ty - 2)
+}
+
+// hex pixels next to a pixel outside of the hexagon make up its outline.
+fn on_hex_edge(tile: usize, tx: usize, ty: usize) -> bool {
+    tx == 0
+        || ty == 0
+        || tx + 1 == tile
+        || ty + 1 == tile
+        || !in_hex(tile, tx - 1, ty)
+        || !in_hex(tile, tx + 1, ty)
+        || !in_hex(tile, tx, ty - 1)
+        || !in_hex(tile, tx, ty + 1)
+}
+
+// draws every cell as a `tile`x`tile` block with digit glyphs, flags, mines on loss and grid lines.
+// tiles too small for glyphs are filled with the cell's plain colour. on hex boards, tiles of 4 or
+// more pixels are drawn as hexagons, with odd rows shifted right by half a tile.
+pub struct TileRenderer {
+    pub tile: usize,
+}
+
+impl TileRenderer {
+    pub fn new(tile: usize) -> Self {
+        TileRenderer { tile: tile.max(1) }
+    }
+
+    fn hex(&self, game_state: &GameState) -> bool {
+        game_state.neighborhood == Neighborhood::Hex && self.tile >= 4
+    }
+
+    // hexagons of neighboring rows interlock, so rows are closer together than a tile.
+    fn row_pitch(&self, game_state: &GameState) -> usize {
+        if self.hex(game_state) {
+            self.tile - self.tile / 4
+        } else {
+            self.tile
+        }
+    }
+
+    // the top left pixel of the tile of a cell.
+    fn origin(&self, game_state: &GameState, x: usize, y: usize) -> (usize, usize) {
+        let shift = if self.hex(game_state) && y % 2 == 1 {
+            self.tile / 2
+        } else {
+            0
+        };
+        (x * self.tile + shift, y * self.row_pitch(game_state))
+    }
+
+    // dimensions of the pixel buffer for `game_state`.
+    pub fn size(&self, game_state: &GameState) -> (usize, usize) {
+        if self.hex(game_state) {
+            (
+                game_state.width * self.tile + self.tile / 2,
+                game_state.height.saturating_sub(1) * self.row_pitch(game_state) + self.tile,
+            )
+        } else {
+            (game_state.width * self.tile, game_state.height * self.tile)
+        }
+    }
+
+    // the cell under a pixel of the buffer, if there is one.
+    pub fn cell_at(&self, game_state: &GameState, pixel: (usize, usize)) -> Option<(usize, usize)> {
+        let (px, py) = pixel;
+        let pitch = self.row_pitch(game_state);
+        // with interlocking rows, the pixel can be in the tile of this row or of the one above it.
+        let rows = [Some(py / pitch), (py / pitch).checked_sub(1)];
+        rows.iter().flatten().find_map(|&y| {
+            let (shift, top) = self.origin(game_state, 0, y);
+            if y >= game_state.height || px < shift || py - top >= self.tile {
+                return None;
+            }
+            let x = (px - shift) / self.tile;
+            let inside =
+                !self.hex(game_state) || in_hex(self.tile, (px - shift) % self.tile, py - top);
+            if x < game_state.width && inside {
+                Some((x, y))
+            } else {
+                None
+            }
+        })
+    }
+
+    // `cell_pixels` holds one colour per cell, as filled by `draw`, `draw_heat_map` or an `Overlay`.
+    // it supplies the background of unknown cells, so overlays carry over to the tiles.
+    pub fn render(&self, game_state: &GameState, cell_pixels: &[u32], tile_pixels: &mut [u32]) {
+        let tile = self.tile;
+        let (stride, _) = self.size(game_state);
+        let hex = self.hex(game_state);
+        let grid_lines = tile >= 4;
+        // the part of the tile inside the grid lines, and how far the 5x5 glyphs can be scaled up in it.
+        let inner = if grid_lines { tile - 1 } else { tile };
+        let scale = inner / 7;
+        let offset = inner.saturating_sub(5 * scale) / 2;
+        if hex {
+            // the gaps along the edges of the board that no hexagon covers.
+            tile_pixels.iter_mut().for_each(|p| *p = GRID_COLOR);
+        }
+
+        for (i, cell) in game_state.field.iter().enumerate() {
+            let (x, y) = (i % game_state.width, i / game_state.width);
+            let (background, glyph) = if scale == 0 {
+                (cell_pixels[i], None)
+            } else {
+                match cell.visibility {
+                    CellVisibility::Empty(0) => (rgb_to_u32(200, 200, 200), None),
+                    // larger neighborhoods can go past the glyphs, those cells keep their plain colour.
+                    CellVisibility::Empty(n) if n > DIGIT_GLYPHS.len() => (cell_pixels[i], None),
+                    CellVisibility::Empty(n) => {
+                        let mut glyph = DIGIT_GLYPHS[n - 1];
+                        // center the 3 pixel wide digits in the 5 pixel wide glyph.
+                        glyph.iter_mut().for_each(|row| *row <<= 1);
+                        (rgb_to_u32(200, 200, 200), Some((glyph, digit_color(n))))
+                    }
+                    CellVisibility::Flagged(_) => (
+                        rgb_to_u32(128, 128, 128),
+                        Some((FLAG_GLYPH, rgb_to_u32(255, 0, 0))),
+                    ),
+                    CellVisibility::Unknown
+                        if cell.state.mines() > 0
+                            && game_state.game_condition == GameCondition::Lost =>
+                    {
+                        (cell_pixels[i], Some((MINE_GLYPH, rgb_to_u32(0, 0, 0))))
+                    }
+                    CellVisibility::Unknown => (cell_pixels[i], None),
+                }
+            };
+
+            let (left, top) = self.origin(game_state, x, y);
+            for ty in 0..tile {
+                for tx in 0..tile {
+                    if hex && !in_hex(tile, tx, ty) {
+                        continue;
+                    }
+                    let edge = if hex {
+                        on_hex_edge(tile, tx, ty)
+                    } else {
+                        grid_lines && (tx == tile - 1 || ty == tile - 1)
+                    };
+                    let color = if edge {
+                        GRID_COLOR
+                    } else {
+                        match glyph {
+                            Some((rows, color))
+                                if scale > 0
+                                    && (offset..offset + 5 * scale).contains(&tx)
+                                    && (offset..offset + 5 * scale).contains(&ty)
+                                    && rows[(ty - offset) / scale]
+                                        & (0b10000 >> ((tx - offset) / scale))
+                                        != 0 =>
+                            {
+                                color
+                            }
+                            _ => background,
+                        }
+                    };
+                    tile_pixels[(top + ty) * stride + left + tx] = color;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_heat_map_shades_unknown_cells() {
+        let game_state: GameState = "3 1 1\n1*#".parse().unwrap();
+        let probabilities = vec![(1, 1.0), (2, 0.0)].into_iter().collect();
+        let mut pixels = vec![0; 3];
+        draw_heat_map(&game_state, &probabilities, &[], &mut pixels);
+        assert_eq!(pixels[0], rgb_to_u32(0, 64, 64));
+        assert_eq!(pixels[1], rgb_to_u32(255, 0, 0));
+        assert_eq!(pixels[2], rgb_to_u32(0, 255, 0));
+
+        let saved_valid_clicks = [Event::Click { pos: (2, 0) }];
+        draw_heat_map(
+            &game_state,
+            &probabilities,
+            &saved_valid_clicks,
+            &mut pixels,
+        );
+        assert_eq!(pixels[2], rgb_to_u32(0, 200, 255));
+        assert_eq!("heat-map".parse(), Ok(RenderMode::HeatMap));
+        assert!("heatmap".parse::<RenderMode>().is_err());
+    }
+
+    #[test]
+    fn test_tile_renderer() {
+        let game_state: GameState = "2 1 1\n1*".parse().unwrap();
+        let mut cell_pixels = vec![0; 2];
+        draw(&game_state, &mut cell_pixels);
+
+        // one pixel tiles are just the plain colours.
+        let renderer = TileRenderer::new(1);
+        let mut pixels = vec![0; 2];
+        renderer.render(&game_state, &cell_pixels, &mut pixels);
+        assert_eq!(pixels, cell_pixels);
+
+        let renderer = TileRenderer::new(8);
+        assert_eq!(renderer.size(&game_state), (16, 8));
+        assert_eq!(renderer.cell_at(&game_state, (9, 3)), Some((1, 0)));
+        assert_eq!(renderer.cell_at(&game_state, (16, 3)), None);
+        let mut pixels = vec![0; 16 * 8];
+        renderer.render(&game_state, &cell_pixels, &mut pixels);
+        // grid lines on the last row and column of each tile.
+        assert_eq!(pixels[7], rgb_to_u32(64, 64, 64));
+        assert_eq!(pixels[7 * 16 + 3], rgb_to_u32(64, 64, 64));
+        // the top of the 1 glyph, scaled by 1 and offset by 1 into the tile.
+        assert_eq!(pixels[16 + 3], digit_color(1));
+        assert_eq!(pixels[16 + 2], rgb_to_u32(200, 200, 200));
+        // the unknown cell keeps its plain colour.
+        assert_eq!(pixels[16 + 8 + 3], rgb_to_u32(128, 128, 128));
+    }
+
+    #[test]
+    fn test_hex_tiles() {
+        let game_state: GameState = "2 2 0 hex\n..\n..".parse().unwrap();
+        let renderer = TileRenderer::new(8);
+        // two rows of 8 pixel hexagons, 6 pixels apart, with the second row shifted by 4 pixels.
+        assert_eq!(renderer.size(&game_state), (20, 14));
+        assert_eq!(renderer.cell_at(&game_state, (4, 3)), Some((0, 0)));
+        assert_eq!(renderer.cell_at(&game_state, (8, 10)), Some((0, 1)));
+        // the corners of a tile belong to the hexagons around it, or to none at all.
+        assert_eq!(renderer.cell_at(&game_state, (0, 7)), None);
+        assert_eq!(renderer.cell_at(&game_state, (1, 0)), None);
+
+        let mut cell_pixels = vec![0; 4];
+        draw(&game_state, &mut cell_pixels);
+        let mut pixels = vec![0; 20 * 14];
+        renderer.render(&game_state, &cell_pixels, &mut pixels);
+        assert_eq!(pixels[0], GRID_COLOR);
+        assert_eq!(pixels[3 * 20 + 4], rgb_to_u32(200, 200, 200));
+        assert_eq!(pixels[10 * 20 + 8], rgb_to_u32(200, 200, 200));
+    }
+}
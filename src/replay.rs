@@ -0,0 +1,198 @@
+// recording and playback of the events applied to a single game.
+// a replay stores the starting board (and the seed that generated it, if known) along with every
+// applied event, when it was applied and which strategy produced it.
+
+use std::{convert::TryFrom, fs, path::Path, time::Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::{DecodeError, GameRecord, FORMAT_VERSION};
+use crate::game::*;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayStep {
+    // milliseconds since the start of the game.
+    pub elapsed_ms: u64,
+    pub event: Event,
+    pub source: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u16,
+    pub seed: Option<u64>,
+    pub board: GameRecord,
+    pub steps: Vec<ReplayStep>,
+}
+
+impl Replay {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Replay, DecodeError> {
+        let replay: Replay = serde_json::from_str(json)?;
+        if replay.version == 0 || replay.version > FORMAT_VERSION {
+            return Err(DecodeError::UnsupportedVersion(replay.version));
+        }
+        Ok(replay)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Replay, DecodeError> {
+        Replay::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+pub struct ReplayRecorder {
+    replay: Replay,
+    start: Instant,
+}
+
+impl ReplayRecorder {
+    // `game_state` should be the board before any event was applied to it.
+    pub fn new(game_state: &GameState, seed: Option<u64>) -> Self {
+        ReplayRecorder {
+            replay: Replay {
+                version: FORMAT_VERSION,
+                seed,
+                board: GameRecord::from(game_state),
+                steps: Vec::new(),
+            },
+            start: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, event: Event, source: &str) {
+        self.replay.steps.push(ReplayStep {
+            elapsed_ms: self.start.elapsed().as_millis() as u64,
+            event,
+            source: source.to_string(),
+        });
+    }
+
+    pub fn finish(self) -> Replay {
+        self.replay
+    }
+}
+
+// steps through a replay, keeping the game state in sync with the current position.
+pub struct ReplayPlayer {
+    replay: Replay,
+    game_state: GameState,
+    position: usize,
+    // whether each applied step changed the board, i.e. whether it has an entry in the undo history.
+    changed: Vec<bool>,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Result<Self, DecodeError> {
+        Ok(ReplayPlayer {
+            game_state: GameState::try_from(replay.board.clone())?,
+            replay,
+            position: 0,
+            changed: Vec::new(),
+        })
+    }
+
+    pub fn game_state(&self) -> &GameState {
+        &self.game_state
+    }
+
+    // number of steps that have been applied to the current game state.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.replay.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replay.steps.is_empty()
+    }
+
+    pub fn step_forward(&mut self) -> Option<&ReplayStep> {
+        let step = self.replay.steps.get(self.position)?;
+        let history_len = self.game_state.history_len();
+        self.game_state.apply(step.event);
+        self.changed
+            .push(self.game_state.history_len() > history_len);
+        self.position += 1;
+        Some(step)
+    }
+
+    // returns the step that was undone.
+    pub fn step_backward(&mut self) -> Option<&ReplayStep> {
+        if self.position == 0 {
+            return None;
+        }
+        if self.changed.pop() == Some(true) {
+            self.game_state.undo();
+        }
+        self.position -= 1;
+        self.replay.steps.get(self.position)
+    }
+
+    // step back to just before the most recent step from `source`, e.g. the last guess that was made.
+    pub fn rewind_before_last(&mut self, source: &str) -> bool {
+        match self.replay.steps[..self.position]
+            .iter()
+            .rposition(|step| step.source == source)
+        {
+            Some(position) => {
+                self.seek(position);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn seek(&mut self, position: usize) {
+        let position = position.min(self.len());
+        while self.position > position {
+            self.step_backward();
+        }
+        while self.position < position {
+            self.step_forward();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record_and_play_back() {
+        let mut game_state: GameState = "3 2 1\n*##\n###".parse().unwrap();
+        let mut recorder = ReplayRecorder::new(&game_state, None);
+        for (event, source) in [
+            (Event::Click { pos: (2, 0) }, "test"),
+            (Event::Click { pos: (1, 0) }, "test"),
+            (Event::Click { pos: (1, 0) }, "test"),
+            (Event::Flag { pos: (0, 0) }, "test"),
+        ] {
+            game_state.apply(event);
+            recorder.record(event, source);
+        }
+        let replay = Replay::from_json(&recorder.finish().to_json()).unwrap();
+
+        let mut player = ReplayPlayer::new(replay).unwrap();
+        while player.step_forward().is_some() {}
+        assert_eq!(player.position(), 4);
+        assert_eq!(player.game_state().to_string(), game_state.to_string());
+        assert_eq!(player.game_state().game_condition, GameCondition::Won);
+
+        let undone = player.step_backward().unwrap().event;
+        assert_eq!(undone, Event::Flag { pos: (0, 0) });
+        assert_eq!(player.game_state().remaining_mines(), 1);
+        assert!(player.rewind_before_last("test"));
+        assert_eq!(player.position(), 2);
+        player.seek(0);
+        assert_eq!(player.game_state().to_string(), "3 2 1\n*##\n###\n");
+    }
+}
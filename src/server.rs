@@ -0,0 +1,518 @@
+// local game server, hosting any number of games at once. every game is a session with an id, and
+// is played over http or over a websocket.
+//
+// http endpoints, with json bodies:
+//   POST   /games             {"width": 9, "height": 9, "mines": 10, "seed": 1}, every field optional
+//                             -> {"id": 1, "observation": {...}}
+//                             the body can also set "topology", "neighborhood", "depth" and
+//                             "mines_per_cell", as in the observations. missing fields are taken
+//                             from the options the server was started with.
+//   GET    /games/<id>        -> observation
+//   POST   /games/<id>/moves  a command of the line protocol, e.g. {"command": "click", "x": 3, "y": 4}
+//                             -> observation
+//   GET    /games/<id>/hint   -> {"event": {...}}, the solver's suggestion. nothing is applied.
+//   DELETE /games/<id>
+// errors are answered with a 4xx status and {"type": "error", "message": "..."}.
+//
+// the server has no authentication, so it only listens on loopback addresses.
+//
+// `GET /games/<id>/ws` upgrades to a websocket that speaks the line protocol from `protocol.rs`:
+// the server sends an observation, then answers every text message with one response.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Cursor},
+    net::{SocketAddr, ToSocketAddrs},
+    sync::{Arc, Mutex},
+};
+
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Server};
+use tungstenite::{handshake::derive_accept_key, protocol::Role, Message, WebSocket};
+
+use crate::game::*;
+use crate::guess::hint;
+use crate::protocol::{BotSession, Command, Observation, Response};
+
+type Session = BotSession<Box<dyn FnMut() -> GameState + Send>>;
+
+// `height` is the height of a single layer, so a 3d board has `height * depth` rows.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameOptions {
+    pub width: usize,
+    pub height: usize,
+    pub mines: usize,
+    pub seed: Option<u64>,
+    pub topology: Topology,
+    pub neighborhood: Neighborhood,
+    pub depth: usize,
+    pub mines_per_cell: usize,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        GameOptions {
+            width: 9,
+            height: 9,
+            mines: 10,
+            seed: None,
+            topology: Topology::BOUNDED,
+            neighborhood: Neighborhood::Moore,
+            depth: 1,
+            mines_per_cell: 1,
+        }
+    }
+}
+
+impl GameOptions {
+    // the same combinations are refused as on the command line.
+    pub fn check(&self) -> Result<(), String> {
+        self.neighborhood.check()?;
+        if self.depth == 0 || self.mines_per_cell == 0 {
+            return Err(String::from(
+                "depth and mines per cell have to be at least 1",
+            ));
+        }
+        self.neighborhood
+            .check_topology(self.topology, self.height)?;
+        // rejects empty boards and sizes that overflow before they are multiplied below.
+        GameState::check_dimensions(
+            self.width,
+            self.height.saturating_mul(self.depth),
+            self.mines,
+            self.mines_per_cell,
+        )
+        .map_err(|e| e.to_string())?;
+        if self.width * self.height * self.depth > 1 << 20 {
+            return Err(String::from("the board should have at most 2^20 cells"));
+        }
+        // every number has to fit the symbols of the board.
+        if self.neighborhood.reach(self.depth) * self.mines_per_cell > MAX_NEIGHBORS {
+            return Err(format!(
+                "numbers around cells with {} mines go past {} with {}",
+                self.mines_per_cell, MAX_NEIGHBORS, self.neighborhood
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn new_game<R: Rng + ?Sized>(&self, rng: &mut R) -> GameState {
+        let mut game_state = GameState::with_rng_multi_mine(
+            self.width,
+            self.height * self.depth,
+            self.mines,
+            self.mines_per_cell,
+            rng,
+        );
+        game_state.topology = self.topology;
+        game_state.neighborhood = self.neighborhood.clone();
+        game_state.depth = self.depth;
+        game_state.flood_fill = true;
+        game_state
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Created {
+    pub id: u64,
+    pub observation: Observation,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hint {
+    pub event: Event,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ServerError {
+    NotFound,
+    BadRequest(String),
+}
+
+impl ServerError {
+    fn status(&self) -> u16 {
+        match self {
+            ServerError::NotFound => 404,
+            ServerError::BadRequest(_) => 400,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            ServerError::NotFound => String::from("not found"),
+            ServerError::BadRequest(message) => message.clone(),
+        }
+    }
+}
+
+// every game hosted by a server, by id. `defaults` fill in whatever a new game leaves out.
+#[derive(Default)]
+pub struct Sessions {
+    sessions: Mutex<BTreeMap<u64, Session>>,
+    next_id: Mutex<u64>,
+    pub defaults: GameOptions,
+}
+
+impl Sessions {
+    pub fn new(defaults: GameOptions) -> Self {
+        Sessions {
+            defaults,
+            ..Sessions::default()
+        }
+    }
+
+    pub fn create(&self, options: GameOptions) -> Result<Created, ServerError> {
+        options.check().map_err(ServerError::BadRequest)?;
+        let mut rng = match options.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        // every game gets its own seed, like the boards of the other modes.
+        let new_game: Box<dyn FnMut() -> GameState + Send> =
+            Box::new(move || options.new_game(&mut StdRng::seed_from_u64(rng.gen())));
+        let session = BotSession::new(new_game);
+        let observation = Observation::from(session.game_state());
+
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+        self.sessions.lock().unwrap().insert(id, session);
+        Ok(Created { id, observation })
+    }
+
+    fn with<T>(&self, id: u64, f: impl FnOnce(&mut Session) -> T) -> Result<T, ServerError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.get_mut(&id).map(f).ok_or(ServerError::NotFound)
+    }
+
+    pub fn observe(&self, id: u64) -> Result<Observation, ServerError> {
+        self.with(id, |session| Observation::from(session.game_state()))
+    }
+
+    pub fn play(&self, id: u64, command: Command) -> Result<Observation, ServerError> {
+        match self.with(id, |session| session.handle(command))? {
+            Ok(Response::Observation(observation)) => Ok(observation),
+            Ok(Response::Error { message }) => Err(ServerError::BadRequest(message)),
+            Err(e) => Err(ServerError::BadRequest(e.to_string())),
+        }
+    }
+
+    pub fn hint(&self, id: u64) -> Result<Hint, ServerError> {
+        // the search can take a while, so it runs on a copy without holding the lock.
+        let game_state = self.with(id, |session| session.game_state().snapshot())?;
+        Ok(Hint {
+            event: hint(&game_state),
+        })
+    }
+
+    pub fn remove(&self, id: u64) -> Result<(), ServerError> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .map(|_| ())
+            .ok_or(ServerError::NotFound)
+    }
+}
+
+fn json_response<T: Serialize>(status: u16, value: &T) -> tiny_http::Response<Cursor<Vec<u8>>> {
+    tiny_http::Response::from_string(serde_json::to_string(value).unwrap())
+        .with_status_code(status)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap())
+}
+
+fn error_response(e: ServerError) -> tiny_http::Response<Cursor<Vec<u8>>> {
+    json_response(
+        e.status(),
+        &Response::Error {
+            message: e.message(),
+        },
+    )
+}
+
+// the options of a new game, starting from the server's defaults. unlike `#[serde(default)]`,
+// this keeps the defaults for fields that the body leaves out.
+fn parse_options(body: &str, defaults: &GameOptions) -> Result<GameOptions, ServerError> {
+    let mut options = serde_json::to_value(defaults).unwrap();
+    let overrides: serde_json::Map<String, serde_json::Value> =
+        parse_body(body, Default::default())?;
+    options.as_object_mut().unwrap().extend(overrides);
+    serde_json::from_value(options).map_err(|e| ServerError::BadRequest(e.to_string()))
+}
+
+fn parse_body<T: for<'de> Deserialize<'de>>(body: &str, empty: T) -> Result<T, ServerError> {
+    if body.trim().is_empty() {
+        return Ok(empty);
+    }
+    serde_json::from_str(body).map_err(|e| ServerError::BadRequest(e.to_string()))
+}
+
+pub struct GameServer {
+    server: Server,
+    sessions: Arc<Sessions>,
+}
+
+impl GameServer {
+    // e.g. `127.0.0.1:8080`. a port of 0 picks any free port, see `local_addr`. anyone who can reach
+    // the server can play and delete every game, so addresses other than loopback are refused.
+    pub fn bind(addr: &str, defaults: GameOptions) -> io::Result<Self> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() || addrs.iter().any(|addr| !addr.ip().is_loopback()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a loopback address", addr),
+            ));
+        }
+        let server = Server::http(&addrs[..]).map_err(|e| io::Error::other(e.to_string()))?;
+        Ok(GameServer {
+            server,
+            sessions: Arc::new(Sessions::new(defaults)),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.server.server_addr().to_ip().unwrap()
+    }
+
+    pub fn sessions(&self) -> &Arc<Sessions> {
+        &self.sessions
+    }
+
+    // serves requests forever, each on its own thread so that websockets and slow hints don't
+    // hold up other clients.
+    pub fn run(&self) {
+        for request in self.server.incoming_requests() {
+            let sessions = self.sessions.clone();
+            std::thread::spawn(move || handle_request(&sessions, request));
+        }
+    }
+}
+
+fn handle_request(sessions: &Sessions, mut request: Request) {
+    let path: Vec<String> = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or("")
+        .split('/')
+        .filter(|e| !e.is_empty())
+        .map(String::from)
+        .collect();
+    let path: Vec<&str> = path.iter().map(|e| e.as_str()).collect();
+    let id = match path.get(1).map(|id| id.parse::<u64>()) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => {
+            let _ = request.respond(error_response(ServerError::NotFound));
+            return;
+        }
+        None => None,
+    };
+
+    if let (Method::Get, ["games", _, "ws"], Some(id)) = (request.method(), &path[..], id) {
+        serve_websocket(sessions, request, id);
+        return;
+    }
+
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut body) {
+        let _ = request.respond(error_response(ServerError::BadRequest(e.to_string())));
+        return;
+    }
+    let response = match (request.method(), &path[..], id) {
+        (Method::Post, ["games"], None) => parse_options(&body, &sessions.defaults)
+            .and_then(|options| sessions.create(options))
+            .map(|created| json_response(201, &created)),
+        (Method::Get, ["games", _], Some(id)) => sessions
+            .observe(id)
+            .map(|observation| json_response(200, &observation)),
+        (Method::Delete, ["games", _], Some(id)) => sessions
+            .remove(id)
+            .map(|_| json_response(200, &serde_json::json!({}))),
+        (Method::Post, ["games", _, "moves"], Some(id)) => parse_body(&body, Command::Observe)
+            .and_then(|command| sessions.play(id, command))
+            .map(|observation| json_response(200, &observation)),
+        (Method::Get, ["games", _, "hint"], Some(id)) => {
+            sessions.hint(id).map(|hint| json_response(200, &hint))
+        }
+        _ => Err(ServerError::NotFound),
+    };
+    let _ = request.respond(response.unwrap_or_else(error_response));
+}
+
+fn serve_websocket(sessions: &Sessions, request: Request, id: u64) {
+    let key = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Sec-WebSocket-Key"))
+        .map(|header| derive_accept_key(header.value.as_str().as_bytes()));
+    let (key, observation) = match (key, sessions.observe(id)) {
+        (Some(key), Ok(observation)) => (key, observation),
+        (None, _) => {
+            let _ = request.respond(error_response(ServerError::BadRequest(String::from(
+                "expected a websocket upgrade",
+            ))));
+            return;
+        }
+        (_, Err(e)) => {
+            let _ = request.respond(error_response(e));
+            return;
+        }
+    };
+    let response = tiny_http::Response::empty(101)
+        .with_header(Header::from_bytes(&b"Sec-WebSocket-Accept"[..], key.as_bytes()).unwrap());
+    let stream = request.upgrade("websocket", response);
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+    let observation = serde_json::to_string(&Response::Observation(observation)).unwrap();
+    if socket.send(Message::Text(observation)).is_err() {
+        return;
+    }
+    while let Ok(message) = socket.read() {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let response = sessions
+            .with(id, |session| session.handle_line(&text))
+            .unwrap_or_else(|e| Response::Error {
+                message: e.message(),
+            });
+        if socket
+            .send(Message::Text(serde_json::to_string(&response).unwrap()))
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    // a minimal http client, so that the test needs nothing but the server itself.
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            addr,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, body.to_string())
+    }
+
+    #[test]
+    fn test_http_and_websocket_sessions() {
+        assert!(GameServer::bind("0.0.0.0:0", GameOptions::default()).is_err());
+        let server = GameServer::bind("127.0.0.1:0", GameOptions::default()).unwrap();
+        let addr = server.local_addr();
+        std::thread::spawn(move || server.run());
+
+        let (status, body) = request(addr, "POST", "/games", r#"{"seed": 1}"#);
+        assert_eq!(status, 201);
+        let created: Created = serde_json::from_str(&body).unwrap();
+        let (_, body) = request(
+            addr,
+            "POST",
+            "/games",
+            r#"{"width": 4, "height": 4, "mines": 3}"#,
+        );
+        let other: Created = serde_json::from_str(&body).unwrap();
+        assert_ne!(created.id, other.id);
+        assert_eq!(other.observation.width, 4);
+        let (status, body) = request(
+            addr,
+            "POST",
+            "/games",
+            r#"{"width": 4, "height": 2, "mines": 3, "depth": 2, "neighborhood": "hex"}"#,
+        );
+        assert_eq!(status, 201);
+        let layered: Created = serde_json::from_str(&body).unwrap();
+        assert_eq!(layered.observation.height, 4);
+        assert_eq!(layered.observation.neighborhood, Neighborhood::Hex);
+        let defaults = GameOptions {
+            mines_per_cell: 2,
+            ..GameOptions::default()
+        };
+        let options = parse_options(r#"{"width": 4}"#, &defaults).unwrap();
+        assert_eq!((options.width, options.mines_per_cell), (4, 2));
+
+        let path = format!("/games/{}/moves", created.id);
+        let (status, body) = request(
+            addr,
+            "POST",
+            &path,
+            r#"{"command": "click", "x": 4, "y": 4}"#,
+        );
+        assert_eq!(status, 200);
+        let observation: Observation = serde_json::from_str(&body).unwrap();
+        assert_ne!(observation.board[4].as_bytes()[4], b'#');
+        let (status, _) = request(
+            addr,
+            "POST",
+            &path,
+            r#"{"command": "flag", "x": 40, "y": 4}"#,
+        );
+        assert_eq!(status, 400);
+
+        let (status, body) = request(addr, "GET", &format!("/games/{}/hint", created.id), "");
+        assert_eq!(status, 200);
+        let hint: Hint = serde_json::from_str(&body).unwrap();
+        assert_ne!(hint.event, Event::None);
+        let (_, body) = request(addr, "GET", &format!("/games/{}", created.id), "");
+        assert_eq!(
+            serde_json::from_str::<Observation>(&body).unwrap(),
+            observation
+        );
+
+        let url = format!("ws://{}/games/{}/ws", addr, other.id);
+        let (mut socket, _) = tungstenite::client(url, TcpStream::connect(addr).unwrap()).unwrap();
+        let first: Response =
+            serde_json::from_str(&socket.read().unwrap().into_text().unwrap()).unwrap();
+        assert_eq!(first, Response::Observation(other.observation));
+        socket
+            .send(Message::Text(String::from(
+                r#"{"command": "unflag", "x": 0, "y": 0}"#,
+            )))
+            .unwrap();
+        let reply: Response =
+            serde_json::from_str(&socket.read().unwrap().into_text().unwrap()).unwrap();
+        assert!(matches!(reply, Response::Error { .. }));
+        socket.close(None).unwrap();
+
+        let (status, _) = request(addr, "DELETE", &format!("/games/{}", created.id), "");
+        assert_eq!(status, 200);
+        let (status, _) = request(addr, "GET", &format!("/games/{}", created.id), "");
+        assert_eq!(status, 404);
+        let (status, _) = request(
+            addr,
+            "POST",
+            "/games",
+            r#"{"width": 2, "height": 2, "mines": 4}"#,
+        );
+        assert_eq!(status, 400);
+        let (status, _) = request(
+            addr,
+            "POST",
+            "/games",
+            r#"{"width": 18446744073709551615, "height": 2, "mines": 1}"#,
+        );
+        assert_eq!(status, 400);
+    }
+}
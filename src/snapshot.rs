@@ -0,0 +1,147 @@
+// headless export of rendered boards to image files, for looking at games without a window.
+// ppm is written by hand, png goes through the `png` crate.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::game::*;
+use crate::render::{draw, Overlay, TileRenderer};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Ppm => "ppm",
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ImageFormat> {
+        path.as_ref().extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "ppm" => Ok(ImageFormat::Ppm),
+            _ => Err(format!(
+                "unknown image format {:?}, expected `png` or `ppm`",
+                s
+            )),
+        }
+    }
+}
+
+// an rgb image in the same 0x00RRGGBB layout as the window buffer.
+pub struct Snapshot {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Snapshot {
+    pub fn new(game_state: &GameState, renderer: &TileRenderer, overlay: Option<&Overlay>) -> Self {
+        let mut cell_pixels = vec![0u32; game_state.width * game_state.height];
+        draw(game_state, &mut cell_pixels);
+        if let Some(overlay) = overlay {
+            overlay.draw(game_state, &mut cell_pixels);
+        }
+        Snapshot::from_cell_pixels(game_state, renderer, &cell_pixels)
+    }
+
+    // for a buffer that was already filled by `draw` or one of the other per cell draw functions.
+    pub fn from_cell_pixels(
+        game_state: &GameState,
+        renderer: &TileRenderer,
+        cell_pixels: &[u32],
+    ) -> Self {
+        let (width, height) = renderer.size(game_state);
+        let mut pixels = vec![0u32; width * height];
+        renderer.render(game_state, cell_pixels, &mut pixels);
+        Snapshot {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn rgb_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 3);
+        for p in self.pixels.iter() {
+            bytes.extend_from_slice(&[(p >> 16) as u8, (p >> 8) as u8, *p as u8]);
+        }
+        bytes
+    }
+
+    pub fn write_ppm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(&self.rgb_bytes())
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgb_bytes())?;
+        Ok(())
+    }
+
+    pub fn write<W: Write>(&self, writer: W, format: ImageFormat) -> io::Result<()> {
+        match format {
+            ImageFormat::Png => self.write_png(writer),
+            ImageFormat::Ppm => self.write_ppm(writer),
+        }
+    }
+
+    // the format is picked from the extension of `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let format = ImageFormat::from_path(&path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "image path should end in .png or .ppm",
+            )
+        })?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer, format)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ppm_and_png_output() {
+        let game_state: GameState = "2 1 1\n1*".parse().unwrap();
+        let snapshot = Snapshot::new(&game_state, &TileRenderer::new(1), None);
+        let mut ppm = Vec::new();
+        snapshot.write_ppm(&mut ppm).unwrap();
+        assert_eq!(ppm, b"P6\n2 1\n255\n\x00\x40\x40\x80\x80\x80");
+
+        let mut png = Vec::new();
+        snapshot.write_png(&mut png).unwrap();
+        let decoder = png::Decoder::new(&png[..]);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buffer).unwrap();
+        assert_eq!(&buffer[..6], &ppm[ppm.len() - 6..]);
+
+        assert_eq!(ImageFormat::from_path("loss.PNG"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path("loss.txt"), None);
+    }
+}
@@ -1,31 +1,78 @@
-use rayon::iter::{
-    IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator,
-};
+use std::collections::{BTreeSet, HashSet};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
 use crate::game::*;
 
 pub trait Strategy {
-    fn attempt(&mut self, game_state: &GameState) -> Vec<Event>;
-    fn update(&mut self, game_state: &GameState, event: Event);
+    fn name(&self) -> &'static str;
+    // called once with the starting position of a game, before any attempt or update.
+    fn init(&mut self, _game_state: &GameState) {}
+    // `cells` are the cells whose neighborhood changed since this strategy's last attempt.
+    fn attempt(&mut self, game_state: &GameState, cells: &[(usize, usize)]) -> Vec<Event>;
+    fn update(&mut self, _game_state: &GameState, _event: Event) {}
+    // drop any state from the previous game. `init` is called again before the next game starts.
+    fn reset(&mut self) {}
+}
+
+// work queue of cells that need to be re-examined, shared by every strategy in a `Solver`.
+// each subscriber gets its own queue, so the cost of an attempt scales with the number of
+// changed cells rather than with the board area.
+#[derive(Default)]
+pub struct Frontier {
+    queues: Vec<Vec<(usize, usize)>>,
+    queued: Vec<HashSet<(usize, usize)>>,
 }
 
-pub struct BijectionDetection {
-    initialized: bool,
-    cells_of_interest: Vec<bool>,
+impl Frontier {
+    pub fn new() -> Self {
+        Frontier::default()
+    }
+
+    pub fn subscribe(&mut self) -> usize {
+        self.queues.push(Vec::new());
+        self.queued.push(HashSet::new());
+        self.queues.len() - 1
+    }
+
+    pub fn mark(&mut self, pos: (usize, usize)) {
+        for (queue, queued) in self.queues.iter_mut().zip(self.queued.iter_mut()) {
+            if queued.insert(pos) {
+                queue.push(pos);
+            }
+        }
+    }
+
+    // mark a cell and all of its neighbors, since a change to a cell can only affect the deductions around it.
+    pub fn mark_around(&mut self, game_state: &GameState, pos: (usize, usize)) {
+        self.mark(pos);
+        for neighbor in game_state.neighbors(pos.0, pos.1) {
+            self.mark(neighbor);
+        }
+    }
+
+    pub fn drain(&mut self, subscriber: usize) -> Vec<(usize, usize)> {
+        self.queued[subscriber].clear();
+        std::mem::take(&mut self.queues[subscriber])
+    }
+
+    pub fn clear(&mut self) {
+        self.queues.iter_mut().for_each(|e| e.clear());
+        self.queued.iter_mut().for_each(|e| e.clear());
+    }
 }
 
+pub struct BijectionDetection;
+
 impl Strategy for BijectionDetection {
-    fn attempt(&mut self, game_state: &GameState) -> Vec<Event> {
-        let width = game_state.width;
-        let first_cells: Vec<Event> = self
-            .cells_of_interest
-            .par_iter_mut()
-            .enumerate()
-            .filter_map(|(i, tracked)| {
-                if !*tracked {
-                    return None;
-                }
-                let (x, y) = (i % width, i / width);
+    fn name(&self) -> &'static str {
+        "BijectionDetection"
+    }
+
+    fn attempt(&mut self, game_state: &GameState, cells: &[(usize, usize)]) -> Vec<Event> {
+        let first_cells: Vec<Event> = cells
+            .par_iter()
+            .filter_map(|&(x, y)| {
                 let center_cell = game_state.at(x, y).unwrap();
                 let mut suggested_cell = Event::None;
                 match center_cell {
@@ -34,112 +81,60 @@ impl Strategy for BijectionDetection {
                         ..
                     } => {
                         // detect one to one correspondence of unclicked cells to number of active unflagged mines.
-                        let unknown_neighbor_count: usize = game_state
-                            .neighbors(x, y)
-                            .iter()
-                            .map(|c| {
-                                if let Some(Cell {
-                                    visibility: CellVisibility::Unknown,
-                                    ..
-                                }) = game_state.at(c.0, c.1)
-                                {
-                                    1usize
-                                } else {
-                                    0usize
-                                }
-                            })
-                            .sum();
-                        let flagged_neighbor_count: usize = game_state
-                            .neighbors(x, y)
-                            .iter()
-                            .map(|c| {
-                                if let Some(Cell {
-                                    visibility: CellVisibility::Flagged,
-                                    ..
-                                }) = game_state.at(c.0, c.1)
-                                {
-                                    1usize
-                                } else {
-                                    0usize
-                                }
-                            })
-                            .sum();
-                        if unknown_neighbor_count + flagged_neighbor_count != num_neighbor_mines {
+                        // a cell that can hold several mines has room for as many as it has missing flags,
+                        // and has to take another flag whenever the room in the other cells falls short.
+                        let room = |cell: Cell| match cell.visibility {
+                            CellVisibility::Empty(_) => 0,
+                            visibility => {
+                                game_state.mines_per_cell.saturating_sub(visibility.flags())
+                            }
+                        };
+                        let mut open_neighbor_count = 0;
+                        let mut flagged_neighbor_count = 0;
+                        for (nx, ny) in game_state.neighbors(x, y) {
+                            let cell = game_state.at(nx, ny).unwrap();
+                            open_neighbor_count += room(cell);
+                            flagged_neighbor_count += cell.visibility.flags();
+                        }
+                        if flagged_neighbor_count >= num_neighbor_mines {
+                            // there's no more neighbor cells to flag
                             return None;
                         }
-                        if flagged_neighbor_count == num_neighbor_mines {
-                            // remove current cell because there's no more neighbor cells to click
-                            *tracked = false;
+                        let unflagged_mines = num_neighbor_mines - flagged_neighbor_count;
+                        if unflagged_mines > open_neighbor_count {
                             return None;
                         }
                         for (nx, ny) in game_state.neighbors(x, y) {
-                            if let Some(cell) = game_state.at(nx, ny) {
-                                if cell.visibility == CellVisibility::Unknown {
-                                    // bijection_opportunities += 1;
-                                    suggested_cell = Event::Flag { pos: (nx, ny) };
-                                    // neighbor_cell = Some((x, y));
-                                    break;
-                                }
+                            let cell = game_state.at(nx, ny).unwrap();
+                            if room(cell) > 0 && unflagged_mines + room(cell) > open_neighbor_count
+                            {
+                                suggested_cell = Event::Flag { pos: (nx, ny) };
+                                break;
                             }
                         }
                     }
-                    _ => *tracked = false,
+                    _ => return None,
                 }
-                // println!("returning from BijectionDetection with {:?} with neighbor {:?} when there were {} useful bijection cells to explore from", suggested_cell, neighbor_cell, bijection_opportunities);
+                // println!("returning from BijectionDetection with {:?}", suggested_cell);
                 Some(suggested_cell)
             })
             .collect();
         first_cells
     }
-    fn update(&mut self, game_state: &GameState, event: Event) {
-        if !self.initialized {
-            // do initialization step.
-            self.cells_of_interest = vec![false; game_state.width * game_state.height];
-            self.initialized = true;
-        } else {
-            // process event to update cells_of_interest, such that useless cells are ignored.
-            match event {
-                Event::Flag { pos } => {
-                    self.cells_of_interest[pos.0 + pos.1 * game_state.width] = false;
-                    for (x, y) in game_state.neighbors(pos.0, pos.1) {
-                        self.cells_of_interest[x + y * game_state.width] = true;
-                    }
-                }
-                Event::Click { pos } => {
-                    self.cells_of_interest[pos.0 + pos.1 * game_state.width] = true;
-                    for (x, y) in game_state.neighbors(pos.0, pos.1) {
-                        self.cells_of_interest[x + y * game_state.width] = true;
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
 }
 
-pub struct ExhaustedCellDetection {
-    initialized: bool,
-    cells_of_interest: Vec<bool>,
-}
+pub struct ExhaustedCellDetection;
 
 impl Strategy for ExhaustedCellDetection {
-    fn attempt(&mut self, game_state: &GameState) -> Vec<Event> {
-        // let mut neighbor_cell = None;
-        // let mut zero_count = 0;
-
-        let first_cells: Vec<Event> = self
-            .cells_of_interest
-            .par_iter_mut()
-            .enumerate()
-            .filter_map(|(i, tracked)| {
-                if !*tracked {
-                    return None;
-                }
-                let mut suggested_cell = Event::None;
+    fn name(&self) -> &'static str {
+        "ExhaustedCellDetection"
+    }
 
-                let (x, y) = (i % game_state.width, i / game_state.width);
+    fn attempt(&mut self, game_state: &GameState, cells: &[(usize, usize)]) -> Vec<Event> {
+        let first_cells: Vec<Event> = cells
+            .par_iter()
+            .filter_map(|&(x, y)| {
                 let center_cell = game_state.at(x, y).unwrap();
-                let mut suggested_cell = Event::None;
                 match center_cell {
                     Cell {
                         visibility: CellVisibility::Empty(num_neighbor_mines),
@@ -149,17 +144,7 @@ impl Strategy for ExhaustedCellDetection {
                         let flagged_neighbor_count: usize = game_state
                             .neighbors(x, y)
                             .iter()
-                            .map(|c| {
-                                if let Some(Cell {
-                                    visibility: CellVisibility::Flagged,
-                                    ..
-                                }) = game_state.at(c.0, c.1)
-                                {
-                                    1usize
-                                } else {
-                                    0usize
-                                }
-                            })
+                            .map(|c| game_state.at(c.0, c.1).unwrap().visibility.flags())
                             .sum();
                         // this cell also has `flagged` flagged mines surrounding it.
                         if num_neighbor_mines != flagged_neighbor_count {
@@ -172,97 +157,431 @@ impl Strategy for ExhaustedCellDetection {
                                 ..
                             }) = game_state.at(*nx, *ny)
                             {
-                                // zero_count += 1;
-                                suggested_cell = Event::Click { pos: (*nx, *ny) };
-                                // neighbor_cell = Some((x, y));
-                                return Some(suggested_cell);
+                                return Some(Event::Click { pos: (*nx, *ny) });
                             }
                         }
 
-                        // remove current cell because there's no more neighbor cells to click and we didn't break.
-                        *tracked = false;
-                        return None;
-                    }
-                    _ => {
-                        *tracked = false;
+                        // there's no more neighbor cells to click.
+                        None
                     }
+                    _ => None,
                 }
-
-                // println!("returning from ZeroNeighborDetection with {:?} with neighbor cell {:?} when there were {} useful zero cells to choose from", suggested_cell, neighbor_cell, zero_count);
-                Some(suggested_cell)
             })
             .collect();
 
         first_cells
     }
-    fn update(&mut self, game_state: &GameState, event: Event) {
-        if !self.initialized {
-            // do initialization step.
-            self.cells_of_interest = vec![false; game_state.width * game_state.height];
-            self.initialized = true;
+}
+
+// the unrevealed neighbors of a revealed cell that can still take a mine, along with how many of its
+// mines are still missing a flag. `None` for cells that aren't revealed or have too many flags.
+fn constraint(game_state: &GameState, x: usize, y: usize) -> Option<(Vec<(usize, usize)>, usize)> {
+    let num_neighbor_mines = match game_state.at(x, y)?.visibility {
+        CellVisibility::Empty(n) => n,
+        _ => return None,
+    };
+    let mut open = Vec::new();
+    let mut flagged = 0;
+    for (nx, ny) in game_state.neighbors(x, y) {
+        let visibility = game_state.at(nx, ny).unwrap().visibility;
+        flagged += visibility.flags();
+        if !matches!(visibility, CellVisibility::Empty(_))
+            && visibility.flags() < game_state.mines_per_cell
+        {
+            open.push((nx, ny));
+        }
+    }
+    num_neighbor_mines
+        .checked_sub(flagged)
+        .map(|unflagged| (open, unflagged))
+}
+
+// when the open neighbors of one revealed cell are a subset of another's, the difference between
+// their unflagged mines has to be in the cells that only the second one touches. e.g. a 1 and a 2
+// next to each other along an edge: the 2 has a mine in the cell that the 1 can't see.
+pub struct SubsetDetection;
+
+impl SubsetDetection {
+    fn compare(
+        game_state: &GameState,
+        (inner, inner_mines): &(Vec<(usize, usize)>, usize),
+        (outer, outer_mines): &(Vec<(usize, usize)>, usize),
+    ) -> Vec<Event> {
+        if inner.is_empty()
+            || inner.len() >= outer.len()
+            || outer_mines < inner_mines
+            || !inner.iter().all(|e| outer.contains(e))
+        {
+            return Vec::new();
+        }
+        let difference: Vec<(usize, usize)> = outer
+            .iter()
+            .filter(|e| !inner.contains(e))
+            .copied()
+            .collect();
+        let room: usize = difference
+            .iter()
+            .map(|(x, y)| {
+                game_state.mines_per_cell - game_state.at(*x, *y).unwrap().visibility.flags()
+            })
+            .sum();
+        let mines = outer_mines - inner_mines;
+        if mines == 0 {
+            difference
+                .into_iter()
+                .filter(|(x, y)| {
+                    game_state.at(*x, *y).unwrap().visibility == CellVisibility::Unknown
+                })
+                .map(|pos| Event::Click { pos })
+                .collect()
+        } else if mines == room {
+            difference
+                .into_iter()
+                .map(|pos| Event::Flag { pos })
+                .collect()
         } else {
-            // process event to update cells_of_interest, such that useless cells are ignored.
-            match event {
-                Event::Flag { pos } => {
-                    self.cells_of_interest[pos.0 + pos.1 * game_state.width] = false;
-                    for (x, y) in game_state.neighbors(pos.0, pos.1) {
-                        self.cells_of_interest[x + y * game_state.width] = true;
-                    }
-                }
-                Event::Click { pos } => {
-                    self.cells_of_interest[pos.0 + pos.1 * game_state.width] = true;
-                    for (x, y) in game_state.neighbors(pos.0, pos.1) {
-                        self.cells_of_interest[x + y * game_state.width] = true;
+            Vec::new()
+        }
+    }
+}
+
+impl Strategy for SubsetDetection {
+    fn name(&self) -> &'static str {
+        "SubsetDetection"
+    }
+
+    fn attempt(&mut self, game_state: &GameState, cells: &[(usize, usize)]) -> Vec<Event> {
+        cells
+            .par_iter()
+            .flat_map_iter(|&(x, y)| {
+                let mut events = Vec::new();
+                let center = match constraint(game_state, x, y) {
+                    Some(center) => center,
+                    None => return events,
+                };
+                // only revealed cells that share an open neighbor with this one can be compared to it.
+                let others: BTreeSet<(usize, usize)> = center
+                    .0
+                    .iter()
+                    .flat_map(|(nx, ny)| game_state.neighbors(*nx, *ny))
+                    .filter(|pos| *pos != (x, y))
+                    .collect();
+                for (ox, oy) in others {
+                    if let Some(other) = constraint(game_state, ox, oy) {
+                        events.extend(SubsetDetection::compare(game_state, &center, &other));
+                        events.extend(SubsetDetection::compare(game_state, &other, &center));
                     }
                 }
-                _ => {}
-            }
+                events
+            })
+            .collect()
+    }
+}
+
+// once the mines that are left can be counted, the rest of the board follows: either every open cell
+// is full of mines, or none of them are. this looks at the whole board, so it isn't part of the
+// default solver, which works from the cells that changed.
+pub struct MineCountDetection;
+
+impl Strategy for MineCountDetection {
+    fn name(&self) -> &'static str {
+        "MineCountDetection"
+    }
+
+    fn attempt(&mut self, game_state: &GameState, _cells: &[(usize, usize)]) -> Vec<Event> {
+        let width = game_state.width;
+        let flags: usize = game_state.field.iter().map(|c| c.visibility.flags()).sum();
+        let unflagged_mines = match game_state.bomb_count.checked_sub(flags) {
+            Some(unflagged_mines) => unflagged_mines,
+            None => return Vec::new(),
+        };
+        let open: Vec<(usize, (usize, usize))> = game_state
+            .field
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !matches!(c.visibility, CellVisibility::Empty(_)))
+            .map(|(i, c)| {
+                let room = game_state
+                    .mines_per_cell
+                    .saturating_sub(c.visibility.flags());
+                (room, (i % width, i / width))
+            })
+            .filter(|(room, _)| *room > 0)
+            .collect();
+        if unflagged_mines == 0 {
+            open.into_iter()
+                .filter(|(_, (x, y))| {
+                    game_state.at(*x, *y).unwrap().visibility == CellVisibility::Unknown
+                })
+                .map(|(_, pos)| Event::Click { pos })
+                .collect()
+        } else if unflagged_mines == open.iter().map(|(room, _)| room).sum::<usize>() {
+            open.into_iter()
+                .map(|(_, pos)| Event::Flag { pos })
+                .collect()
+        } else {
+            Vec::new()
         }
     }
 }
 
+// everything the built in strategies can prove about a position without applying any moves.
+#[derive(Debug, Default, PartialEq)]
+pub struct Deductions {
+    pub safe: BTreeSet<(usize, usize)>,
+    pub mines: BTreeSet<(usize, usize)>,
+}
+
 pub struct Solver {
-    // add various internal trackers
-    strategies: Vec<Box<dyn Strategy>>,
+    frontier: Frontier,
+    // each strategy is paired with its subscription to the frontier.
+    strategies: Vec<(usize, Box<dyn Strategy>)>,
 }
 
 impl Solver {
     pub fn new() -> Self {
-        let mut solvers: Vec<Box<dyn Strategy>> = Vec::new();
-        solvers.push(Box::new(ExhaustedCellDetection {
-            initialized: false,
-            cells_of_interest: vec![],
-        }));
-        solvers.push(Box::new(BijectionDetection {
-            initialized: false,
-            cells_of_interest: vec![],
-        }));
-        Solver {
-            strategies: solvers,
+        let mut solver = Solver {
+            frontier: Frontier::new(),
+            strategies: Vec::new(),
+        };
+        solver.add_strategy(Box::new(ExhaustedCellDetection));
+        solver.add_strategy(Box::new(BijectionDetection));
+        solver
+    }
+
+    pub fn add_strategy(&mut self, strategy: Box<dyn Strategy>) {
+        let subscriber = self.frontier.subscribe();
+        self.strategies.push((subscriber, strategy));
+    }
+
+    // prepare for a game starting from `game_state`, which may already have revealed cells.
+    pub fn init(&mut self, game_state: &GameState) {
+        let width = game_state.width;
+        for (i, cell) in game_state.field.iter().enumerate() {
+            if let CellVisibility::Empty(_) = cell.visibility {
+                self.frontier.mark((i % width, i / width));
+            }
+        }
+        for (_, solver) in self.strategies.iter_mut() {
+            solver.init(game_state);
+        }
+    }
+
+    pub fn reset(&mut self, game_state: &GameState) {
+        self.frontier.clear();
+        for (_, solver) in self.strategies.iter_mut() {
+            solver.reset();
         }
+        self.init(game_state);
     }
 
     pub fn next_clicks(&mut self, game_state: &GameState) -> Vec<Event> {
-        let events: Vec<Event> = (&mut self.strategies)
+        self.next_moves(game_state)
+            .into_iter()
+            .map(|(event, _)| event)
+            .collect()
+    }
+
+    // like `next_clicks`, but each event is paired with the name of the strategy that produced it.
+    pub fn next_moves(&mut self, game_state: &GameState) -> Vec<(Event, &'static str)> {
+        let frontier = &mut self.frontier;
+        let events: Vec<(Event, &'static str)> = self
+            .strategies
             .iter_mut() // mutably iterate over strategies
-            .map(|solver| solver.attempt(game_state)) // attempt to solve with each strategy
-            .flatten() // flatten to a iterator of events
-            .collect::<Vec<Event>>() // collect
-            .par_iter() // parallel iterate
-            .filter(|e| match &&e {
-                // filter out None events
-                Event::None => false,
-                _ => true,
+            .flat_map(|(subscriber, solver)| {
+                // attempt to solve with each strategy, using only the cells that changed since its last attempt
+                let cells = frontier.drain(*subscriber);
+                let name = solver.name();
+                solver
+                    .attempt(game_state, &cells)
+                    .into_iter()
+                    .map(move |e| (e, name))
             })
-            .map(move |&e| e) // dereference/copy
+            .filter(|(e, _)| *e != Event::None) // filter out None events
+            .collect();
+        // several cells can suggest the same move. flagging a cell that holds several mines twice
+        // would add two flags, so each move is only kept once.
+        let mut seen = HashSet::new();
+        let events: Vec<(Event, &'static str)> = events
+            .into_iter()
+            .filter(|(e, _)| seen.insert(*e))
             .collect();
         // println!("{}", events.len());
         events
     }
 
     pub fn update(&mut self, game_state: &GameState, event: Event) {
-        for solver in self.strategies.iter_mut() {
-            solver.update(&game_state, event);
+        match event {
+            Event::Flag { pos } | Event::Unflag { pos } | Event::Click { pos } => {
+                self.frontier.mark_around(game_state, pos)
+            }
+            Event::Chord { pos } => {
+                // a chord reveals the neighbors of `pos`, which affects the cells around each of them.
+                for neighbor in game_state.neighbors(pos.0, pos.1) {
+                    self.frontier.mark_around(game_state, neighbor);
+                }
+            }
+            Event::None => {}
         }
+        for (_, solver) in self.strategies.iter_mut() {
+            solver.update(game_state, event);
+        }
+    }
+
+    // like `update`, but marks exactly the cells that the move changed, including the ones revealed by
+    // a flood fill.
+    pub fn update_from(&mut self, game_state: &GameState, outcome: &MoveOutcome) {
+        for pos in outcome.cells() {
+            self.frontier.mark_around(game_state, pos);
+        }
+        for (_, solver) in self.strategies.iter_mut() {
+            solver.update(game_state, outcome.event);
+        }
+    }
+    // apply the rules of `ExhaustedCellDetection` and `BijectionDetection` to a fixpoint, treating
+    // proven cells as if they had already been clicked or flagged. `mines` are the cells proven to be
+    // full, including partly flagged cells on boards that hold several mines per cell.
+    pub fn deductions(game_state: &GameState) -> Deductions {
+        let mut deductions = Deductions::default();
+        let width = game_state.width;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, cell) in game_state.field.iter().enumerate() {
+                let num_neighbor_mines = match cell.visibility {
+                    CellVisibility::Empty(n) => n,
+                    _ => continue,
+                };
+                let (x, y) = (i % width, i / width);
+                let full = game_state.mines_per_cell;
+                let mut known_mines = 0;
+                // room for mines left in cells that aren't known to be full.
+                let mut open = 0;
+                let mut unknown = Vec::new();
+                let mut partly_flagged = Vec::new();
+                for (nx, ny) in game_state.neighbors(x, y) {
+                    match game_state.at(nx, ny).unwrap().visibility {
+                        _ if deductions.mines.contains(&(nx, ny)) => known_mines += full,
+                        CellVisibility::Flagged(flags) => {
+                            known_mines += flags;
+                            if flags < full {
+                                open += full - flags;
+                                partly_flagged.push((nx, ny));
+                            }
+                        }
+                        CellVisibility::Unknown if !deductions.safe.contains(&(nx, ny)) => {
+                            open += full;
+                            unknown.push((nx, ny))
+                        }
+                        _ => {}
+                    }
+                }
+                if known_mines == num_neighbor_mines {
+                    // partly flagged cells hold exactly their flags, but can't be clicked.
+                    if !unknown.is_empty() {
+                        deductions.safe.extend(unknown);
+                        changed = true;
+                    }
+                } else if open > 0 && known_mines + open == num_neighbor_mines {
+                    deductions.mines.extend(unknown);
+                    deductions.mines.extend(partly_flagged);
+                    changed = true;
+                }
+            }
+        }
+        deductions
+    }
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Solver::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frontier_queues_each_cell_once_per_subscriber() {
+        let mut frontier = Frontier::new();
+        let first = frontier.subscribe();
+        let second = frontier.subscribe();
+        frontier.mark((1, 1));
+        frontier.mark((2, 1));
+        frontier.mark((1, 1));
+        assert_eq!(frontier.drain(first), vec![(1, 1), (2, 1)]);
+        assert!(frontier.drain(first).is_empty());
+        frontier.mark((1, 1));
+        assert_eq!(frontier.drain(second), vec![(1, 1), (2, 1)]);
+        assert_eq!(frontier.drain(first), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn test_init_picks_up_revealed_cells() {
+        let mut game_state = GameState::new(3, 3, 0);
+        game_state.field[0].state = CellState::Mine(1);
+        game_state.bomb_count = 1;
+        game_state.click(2, 2);
+
+        let mut solver = Solver::new();
+        assert!(solver.next_clicks(&game_state).is_empty());
+        solver.init(&game_state);
+        let events = solver.next_clicks(&game_state);
+        assert!(events.contains(&Event::Click { pos: (1, 1) }));
+
+        // after a reset, the same position is examined again from scratch.
+        solver.reset(&game_state);
+        assert_eq!(solver.next_clicks(&game_state), events);
+    }
+
+    #[test]
+    fn test_strategies_on_drawn_position() {
+        let mut game_state: GameState = "3 2 1\n*#.\n11.".parse().unwrap();
+        let mut solver = Solver::new();
+        solver.init(&game_state);
+        // only the exhausted zeroes on the right lead anywhere.
+        let events = solver.next_clicks(&game_state);
+        assert!(!events.is_empty());
+        assert!(events.iter().all(|e| *e == Event::Click { pos: (1, 0) }));
+
+        game_state.click(1, 0);
+        solver.update(&game_state, Event::Click { pos: (1, 0) });
+        // now the 1 at (0, 1) has a single unknown neighbor left.
+        assert!(solver
+            .next_clicks(&game_state)
+            .contains(&Event::Flag { pos: (0, 0) }));
+    }
+
+    #[test]
+    fn test_deductions_chain_without_applying_moves() {
+        // the 1 at (1, 1) proves (0, 0) is a mine, which in turn proves (1, 0) and (2, 0) are safe.
+        let game_state: GameState = "3 3 1\n*##\n#1.\n...".parse().unwrap();
+        let before = game_state.to_string();
+        let deductions = Solver::deductions(&game_state);
+        assert_eq!(deductions.mines, vec![(0, 0)].into_iter().collect());
+        assert_eq!(
+            deductions.safe,
+            vec![(1, 0), (2, 0), (0, 1)].into_iter().collect()
+        );
+        assert_eq!(game_state.to_string(), before);
+    }
+
+    #[test]
+    fn test_subset_detection() {
+        // each 1 sees one of the 2's mines, so the 2's third cell has the other one.
+        let game_state: GameState = "3 3 2\n*#*\n121\n...".parse().unwrap();
+        let mut solver = Solver::new();
+        solver.init(&game_state);
+        assert!(solver.next_clicks(&game_state).is_empty());
+
+        let mut solver = Solver::new();
+        solver.add_strategy(Box::new(SubsetDetection));
+        solver.init(&game_state);
+        let events = solver.next_clicks(&game_state);
+        assert!(events.contains(&Event::Flag { pos: (0, 0) }));
+        assert!(events.contains(&Event::Flag { pos: (2, 0) }));
+        assert!(!events.contains(&Event::Flag { pos: (1, 0) }));
     }
 }
@@ -0,0 +1,431 @@
+// human readable board format, e.g.
+//
+// 4 3 2
+// *2.#
+// F2..
+// 11..
+//
+// the header is `<width> <height> <mines>`, optionally followed by a neighborhood (`hex`,
+// `von-neumann`, `knight`, `radius-<n>` or `kernel:<dx>,<dy>;...`) and a topology (`wrap-x`, `wrap-y`
+// or `torus`) for boards whose edges wrap around, and `depth-<d>` for 3d boards, whose `<height>` rows
+// are split into `<d>` layers. layers may be separated by blank lines. `mines-per-cell-<n>` lets a cell
+// hold up to `<n>` mines. then comes one line per row:
+//   `#` unknown cell without a mine
+//   `*` unknown cell with a mine
+//   `F` flagged cell with a mine
+//   `f` flagged cell without a mine (a wrong flag)
+//   `.` or `0` revealed cell with no neighboring mines
+//   `1`-`9` revealed cell with that many neighboring mines
+//   `a`-`z` revealed cell with 10 to 34 neighboring mines, skipping `f`, for larger neighborhoods
+// a cell with several mines or flags lists one symbol per mine or flag in braces: `*` for each
+// unflagged mine, `F` for each flagged mine and `f` for each extra flag, e.g. `{F*}` is a cell with two
+// mines and one flag.
+
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::game::*;
+
+// the symbols for 10 neighboring mines and up. `f` is taken by wrong flags.
+const NUMBER_LETTERS: &str = "abcdeghijklmnopqrstuvwxyz";
+
+// the symbol of a revealed cell with `n` neighboring mines, `?` past `MAX_NEIGHBORS`.
+pub fn number_symbol(n: usize) -> char {
+    match n {
+        0 => '.',
+        1..=9 => std::char::from_digit(n as u32, 10).unwrap(),
+        _ => NUMBER_LETTERS.chars().nth(n - 10).unwrap_or('?'),
+    }
+}
+
+// the number shown by a revealed cell's symbol, if it is one.
+pub fn symbol_number(symbol: char) -> Option<usize> {
+    match symbol {
+        '.' => Some(0),
+        '0'..='9' => symbol.to_digit(10).map(|n| n as usize),
+        _ => NUMBER_LETTERS.find(symbol).map(|i| i + 10),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TextFormatError {
+    MissingHeader,
+    InvalidHeader(String),
+    RowCount {
+        expected: usize,
+        found: usize,
+    },
+    RowLength {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    InvalidSymbol {
+        line: usize,
+        column: usize,
+        symbol: char,
+    },
+    InconsistentNumber {
+        line: usize,
+        column: usize,
+        written: usize,
+        actual: usize,
+    },
+    MineCount {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for TextFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextFormatError::MissingHeader => {
+                write!(f, "missing `<width> <height> <mines>` header")
+            }
+            TextFormatError::InvalidHeader(header) => write!(
+                f,
+                "invalid header {:?}, expected `<width> <height> <mines>`",
+                header
+            ),
+            TextFormatError::RowCount { expected, found } => {
+                write!(f, "expected {} rows, found {}", expected, found)
+            }
+            TextFormatError::RowLength {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} cells, found {}",
+                line, expected, found
+            ),
+            TextFormatError::InvalidSymbol {
+                line,
+                column,
+                symbol,
+            } => write!(
+                f,
+                "line {}, column {}: invalid symbol {:?}",
+                line, column, symbol
+            ),
+            TextFormatError::InconsistentNumber {
+                line,
+                column,
+                written,
+                actual,
+            } => write!(
+                f,
+                "line {}, column {}: cell shows {} but has {} neighboring mines",
+                line, column, written, actual
+            ),
+            TextFormatError::MineCount { expected, found } => write!(
+                f,
+                "header says {} mines but the grid contains {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl Error for TextFormatError {}
+
+impl FromStr for GameState {
+    type Err = TextFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim_end()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let (_, header) = lines.next().ok_or(TextFormatError::MissingHeader)?;
+        let tokens: Vec<&str> = header.split_whitespace().collect();
+        let invalid_header = || TextFormatError::InvalidHeader(header.to_string());
+        if tokens.len() < 3 {
+            return Err(invalid_header());
+        }
+        let numbers = tokens[..3]
+            .iter()
+            .map(|e| e.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid_header())?;
+        let (width, height, mines) = (numbers[0], numbers[1], numbers[2]);
+        if width == 0 || height == 0 {
+            return Err(invalid_header());
+        }
+        let mut topology = Topology::BOUNDED;
+        let mut neighborhood = Neighborhood::Moore;
+        let mut depth = 1;
+        let mut mines_per_cell = 1;
+        for token in tokens[3..].iter() {
+            if let Some(n) = token.strip_prefix("mines-per-cell-") {
+                mines_per_cell = n.parse().map_err(|_| invalid_header())?;
+                if mines_per_cell == 0 {
+                    return Err(invalid_header());
+                }
+            } else if let Some(d) = token.strip_prefix("depth-") {
+                depth = d.parse().map_err(|_| invalid_header())?;
+                if depth == 0 || !height.is_multiple_of(depth) {
+                    return Err(invalid_header());
+                }
+            } else if let Ok(t) = token.parse() {
+                topology = t;
+            } else {
+                neighborhood = token.parse().map_err(|_| invalid_header())?;
+            }
+        }
+
+        // every number has to have a symbol, even when all the neighbors are full of mines.
+        if neighborhood.reach(depth) * mines_per_cell > MAX_NEIGHBORS {
+            return Err(invalid_header());
+        }
+        neighborhood
+            .check_topology(topology, height / depth)
+            .map_err(|_| invalid_header())?;
+
+        let rows: Vec<(usize, &str)> = lines.collect();
+        if rows.len() != height {
+            return Err(TextFormatError::RowCount {
+                expected: height,
+                found: rows.len(),
+            });
+        }
+
+        // first pass: mines and visibility. numbers are checked once every mine is known.
+        let mut field = Vec::with_capacity(width * height);
+        let mut numbers = Vec::new();
+        for (line, row) in rows.iter() {
+            let cells = split_cells(*line, row)?;
+            if cells.len() != width {
+                return Err(TextFormatError::RowLength {
+                    line: *line,
+                    expected: width,
+                    found: cells.len(),
+                });
+            }
+            for (column, symbols) in cells.into_iter() {
+                let symbol = symbols[0];
+                let (state, visibility) = match symbol {
+                    '#' => (CellState::Empty, CellVisibility::Unknown),
+                    '*' | 'F' | 'f' => stacked_cell(&symbols),
+                    _ if symbols.len() == 1 && symbol_number(symbol).is_some() => {
+                        let n = symbol_number(symbol).unwrap();
+                        numbers.push((*line, column, field.len(), n));
+                        (CellState::Empty, CellVisibility::Empty(n))
+                    }
+                    _ => {
+                        return Err(TextFormatError::InvalidSymbol {
+                            line: *line,
+                            column: column + 1,
+                            symbol,
+                        })
+                    }
+                };
+                field.push(Cell { state, visibility });
+            }
+        }
+
+        // each flag covers one mine, so a cell can't take more flags than mines either.
+        let flags_per_cell = field.iter().map(|c| c.visibility.flags()).max();
+        let mut game_state = GameState::from_field(width, height, field);
+        if game_state.mines_per_cell > mines_per_cell || flags_per_cell > Some(mines_per_cell) {
+            return Err(invalid_header());
+        }
+        game_state.topology = topology;
+        game_state.neighborhood = neighborhood;
+        game_state.depth = depth;
+        game_state.mines_per_cell = mines_per_cell;
+        if game_state.bomb_count != mines {
+            return Err(TextFormatError::MineCount {
+                expected: mines,
+                found: game_state.bomb_count,
+            });
+        }
+        for (line, column, i, written) in numbers {
+            let actual = game_state.neighboring_mines(i % width, i / width);
+            if actual != written {
+                return Err(TextFormatError::InconsistentNumber {
+                    line,
+                    column: column + 1,
+                    written,
+                    actual,
+                });
+            }
+        }
+        Ok(game_state)
+    }
+}
+
+// split a row into its cells, each paired with the 0 based column where it starts. a braced group
+// counts as a single cell.
+fn split_cells(line: usize, row: &str) -> Result<Vec<(usize, Vec<char>)>, TextFormatError> {
+    let mut cells = Vec::new();
+    let mut symbols = row.chars().enumerate();
+    while let Some((column, symbol)) = symbols.next() {
+        if symbol != '{' {
+            cells.push((column, vec![symbol]));
+            continue;
+        }
+        let mut group = Vec::new();
+        loop {
+            match symbols.next() {
+                Some((_, '}')) if !group.is_empty() => break,
+                Some((_, symbol @ ('*' | 'F' | 'f'))) => group.push(symbol),
+                Some((column, symbol)) => {
+                    return Err(TextFormatError::InvalidSymbol {
+                        line,
+                        column: column + 1,
+                        symbol,
+                    })
+                }
+                // an unclosed group is reported at its opening brace.
+                None => {
+                    return Err(TextFormatError::InvalidSymbol {
+                        line,
+                        column: column + 1,
+                        symbol,
+                    })
+                }
+            }
+        }
+        cells.push((column, group));
+    }
+    Ok(cells)
+}
+
+// a cell made of `*`, `F` and `f` symbols.
+fn stacked_cell(symbols: &[char]) -> (CellState, CellVisibility) {
+    let mines = symbols.iter().filter(|e| **e != 'f').count();
+    let flags = symbols.iter().filter(|e| **e != '*').count();
+    let state = match mines {
+        0 => CellState::Empty,
+        mines => CellState::Mine(mines),
+    };
+    let visibility = match flags {
+        0 => CellVisibility::Unknown,
+        flags => CellVisibility::Flagged(flags),
+    };
+    (state, visibility)
+}
+
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.width, self.height, self.bomb_count)?;
+        if self.neighborhood != Neighborhood::Moore {
+            write!(f, " {}", self.neighborhood)?;
+        }
+        if self.topology != Topology::BOUNDED {
+            write!(f, " {}", self.topology)?;
+        }
+        if self.depth > 1 {
+            write!(f, " depth-{}", self.depth)?;
+        }
+        if self.mines_per_cell > 1 {
+            write!(f, " mines-per-cell-{}", self.mines_per_cell)?;
+        }
+        writeln!(f)?;
+        for (y, row) in self.field.chunks(self.width).enumerate() {
+            if y > 0 && y.is_multiple_of(self.layer_height()) {
+                writeln!(f)?;
+            }
+            for cell in row {
+                if let CellVisibility::Empty(n) = cell.visibility {
+                    write!(f, "{}", number_symbol(n))?;
+                    continue;
+                }
+                let correct = cell.correct_flags();
+                let symbols: String = std::iter::repeat_n('F', correct)
+                    .chain(std::iter::repeat_n('*', cell.state.mines() - correct))
+                    .chain(std::iter::repeat_n('f', cell.visibility.flags() - correct))
+                    .collect();
+                match symbols.len() {
+                    0 => write!(f, "#")?,
+                    1 => write!(f, "{}", symbols)?,
+                    _ => write!(f, "{{{}}}", symbols)?,
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = "4 3 2\n*2.#\nF2..\n11..\n";
+        let game_state: GameState = text.parse().unwrap();
+        assert_eq!(game_state.bomb_count, 2);
+        assert_eq!(game_state.remaining_mines(), 1);
+        assert_eq!(
+            game_state.at(1, 0).unwrap().visibility,
+            CellVisibility::Empty(2)
+        );
+        assert_eq!(game_state.to_string(), text);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "".parse::<GameState>().err(),
+            Some(TextFormatError::MissingHeader)
+        );
+        assert_eq!(
+            "0 0 0".parse::<GameState>().err(),
+            Some(TextFormatError::InvalidHeader(String::from("0 0 0")))
+        );
+        assert_eq!(
+            "2 1 2\n*#".parse::<GameState>().err(),
+            Some(TextFormatError::MineCount {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            "2 1 0\n#?".parse::<GameState>().err(),
+            Some(TextFormatError::InvalidSymbol {
+                line: 2,
+                column: 2,
+                symbol: '?'
+            })
+        );
+        assert_eq!(
+            "2 2 1\n*2\n##".parse::<GameState>().err(),
+            Some(TextFormatError::InconsistentNumber {
+                line: 2,
+                column: 2,
+                written: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            "2 2 1\n*.\n##".parse::<GameState>().err(),
+            Some(TextFormatError::InconsistentNumber {
+                line: 2,
+                column: 2,
+                written: 0,
+                actual: 1
+            })
+        );
+        // odd hex rows don't line up when they wrap.
+        let text = "4 3 0 hex torus\n####\n####\n####";
+        assert_eq!(
+            text.parse::<GameState>().err(),
+            Some(TextFormatError::InvalidHeader(String::from(
+                "4 3 0 hex torus"
+            )))
+        );
+        assert!("4 3 0 hex wrap-x\n####\n####\n####"
+            .parse::<GameState>()
+            .is_ok());
+        // a second flag on a cell that holds at most one mine.
+        assert_eq!(
+            "2 1 1\n{Ff}#".parse::<GameState>().err(),
+            Some(TextFormatError::InvalidHeader(String::from("2 1 1")))
+        );
+    }
+}
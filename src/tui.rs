@@ -0,0 +1,205 @@
+// terminal frontend, for machines without a display. the board is drawn with ansi colours, two
+// columns per cell. boards that don't fit in the terminal scroll to keep the cursor in view, and are
+// cut off when there is no cursor. odd rows of hex boards are shifted right by one column, half a cell.
+
+use std::{
+    io::{self, Stdout, Write},
+    time::Duration,
+};
+
+use crossterm::{
+    cursor,
+    event::{self, Event as TermEvent, KeyEventKind},
+    execute, queue,
+    style::{Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+
+use crate::game::*;
+use crate::text_format::number_symbol;
+
+pub use crossterm::event::KeyCode;
+
+fn digit_color(n: usize) -> Color {
+    match n {
+        1 => Color::Blue,
+        2 => Color::Green,
+        3 => Color::Red,
+        4 => Color::DarkBlue,
+        5 => Color::DarkRed,
+        6 => Color::Cyan,
+        7 => Color::Magenta,
+        _ => Color::Grey,
+    }
+}
+
+// the symbol and colour of a cell. mines are only shown once the game has been lost.
+pub fn cell_symbol(game_state: &GameState, x: usize, y: usize) -> (char, Color) {
+    let cell = game_state.at(x, y).unwrap();
+    match cell.visibility {
+        CellVisibility::Unknown
+            if cell.state.mines() > 0 && game_state.game_condition == GameCondition::Lost =>
+        {
+            ('*', Color::Yellow)
+        }
+        CellVisibility::Unknown => ('#', Color::DarkGrey),
+        CellVisibility::Flagged(_) => ('F', Color::Red),
+        CellVisibility::Empty(0) => ('.', Color::DarkGrey),
+        CellVisibility::Empty(n) => (number_symbol(n), digit_color(n)),
+    }
+}
+
+// puts the terminal in raw mode on an alternate screen for as long as it is alive.
+pub struct Terminal {
+    stdout: Stdout,
+    // the top left cell that is drawn.
+    origin: (usize, usize),
+}
+
+impl Terminal {
+    pub fn new() -> io::Result<Self> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Terminal {
+            stdout,
+            origin: (0, 0),
+        })
+    }
+
+    // `cursor` is highlighted, and `status` is shown on the line below the board.
+    pub fn draw(
+        &mut self,
+        game_state: &GameState,
+        cursor: Option<(usize, usize)>,
+        status: &str,
+    ) -> io::Result<()> {
+        let (columns, rows) = terminal::size()?;
+        let hex = game_state.neighborhood == Neighborhood::Hex;
+        let visible_width = game_state
+            .width
+            .min((columns as usize).saturating_sub(hex as usize) / 2);
+        let visible_height = game_state.height.min((rows as usize).saturating_sub(2));
+        if let Some((x, y)) = cursor {
+            self.origin = (
+                scroll(self.origin.0, x, visible_width, game_state.width),
+                scroll(self.origin.1, y, visible_height, game_state.height),
+            );
+        } else {
+            self.origin = (0, 0);
+        }
+        queue!(
+            self.stdout,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0)
+        )?;
+        for row in 0..visible_height {
+            let y = self.origin.1 + row;
+            let shift = (hex && y % 2 == 1) as u16;
+            queue!(self.stdout, cursor::MoveTo(shift, row as u16))?;
+            for x in self.origin.0..self.origin.0 + visible_width {
+                let (symbol, color) = cell_symbol(game_state, x, y);
+                if cursor == Some((x, y)) {
+                    queue!(self.stdout, SetBackgroundColor(Color::White))?;
+                }
+                queue!(
+                    self.stdout,
+                    SetForegroundColor(color),
+                    Print(' '),
+                    Print(symbol),
+                    ResetColor
+                )?;
+            }
+        }
+        queue!(
+            self.stdout,
+            cursor::MoveTo(0, visible_height as u16),
+            SetAttribute(crossterm::style::Attribute::Bold),
+            // mines minus flags, since counting only correct flags would give wrong flags away.
+            Print(format!(
+                "{:?}, {} mines left. {}",
+                game_state.game_condition,
+                game_state.mines_left(),
+                status
+            )),
+            SetAttribute(crossterm::style::Attribute::Reset)
+        )?;
+        self.stdout.flush()
+    }
+
+    // waits up to `timeout` for a key press.
+    pub fn poll_key(&self, timeout: Duration) -> io::Result<Option<KeyCode>> {
+        if !event::poll(timeout)? {
+            return Ok(None);
+        }
+        match event::read()? {
+            TermEvent::Key(key) if key.kind == KeyEventKind::Press => Ok(Some(key.code)),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(self.stdout, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// the first of `visible` rows or columns out of `size` to draw, moved as little as possible to keep
+// `cursor` in view.
+pub fn scroll(origin: usize, cursor: usize, visible: usize, size: usize) -> usize {
+    let origin = origin.min(size.saturating_sub(visible));
+    if visible == 0 || cursor < origin {
+        cursor.min(origin)
+    } else if cursor >= origin + visible {
+        cursor + 1 - visible
+    } else {
+        origin
+    }
+}
+
+// moves `cursor` one cell for the arrow keys and `hjkl`, staying on the board.
+pub fn move_cursor(game_state: &GameState, cursor: &mut (usize, usize), key: KeyCode) -> bool {
+    let (x, y) = *cursor;
+    *cursor = match key {
+        KeyCode::Left | KeyCode::Char('h') => (x.saturating_sub(1), y),
+        KeyCode::Right | KeyCode::Char('l') => ((x + 1).min(game_state.width - 1), y),
+        KeyCode::Up | KeyCode::Char('k') => (x, y.saturating_sub(1)),
+        KeyCode::Down | KeyCode::Char('j') => (x, (y + 1).min(game_state.height - 1)),
+        _ => return false,
+    };
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cells_and_cursor() {
+        let mut game_state: GameState = "3 1 1\n1*#".parse().unwrap();
+        assert_eq!(cell_symbol(&game_state, 0, 0), ('1', Color::Blue));
+        assert_eq!(cell_symbol(&game_state, 1, 0), ('#', Color::DarkGrey));
+
+        let mut cursor = (0, 0);
+        assert!(move_cursor(&game_state, &mut cursor, KeyCode::Left));
+        assert_eq!(cursor, (0, 0));
+        move_cursor(&game_state, &mut cursor, KeyCode::Char('l'));
+        move_cursor(&game_state, &mut cursor, KeyCode::Down);
+        assert_eq!(cursor, (1, 0));
+        assert!(!move_cursor(&game_state, &mut cursor, KeyCode::Char('x')));
+
+        // 10 of 100 rows fit, and the view follows the cursor past either edge.
+        assert_eq!(scroll(0, 5, 10, 100), 0);
+        assert_eq!(scroll(0, 10, 10, 100), 1);
+        assert_eq!(scroll(40, 35, 10, 100), 35);
+        assert_eq!(scroll(95, 99, 10, 100), 90);
+        assert_eq!(scroll(0, 2, 10, 3), 0);
+
+        // mines show up once the game is lost.
+        game_state.click(1, 0);
+        assert_eq!(cell_symbol(&game_state, 1, 0), ('*', Color::Yellow));
+        assert_eq!(cell_symbol(&game_state, 2, 0), ('#', Color::DarkGrey));
+    }
+}